@@ -0,0 +1,364 @@
+use crate::dwarf_data::{Encoding, Type};
+use crate::inferior::Inferior;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub addr: usize,
+    /// The location as the user typed it (e.g. `func1` or `*0x401136`)
+    pub location: String,
+    pub enabled: bool,
+    pub hit_count: usize,
+    /// Only stop if this C expression is nonzero
+    pub condition: Option<String>,
+    /// Number of upcoming hits to skip without stopping
+    pub ignore_count: usize,
+    /// Delete the breakpoint the first time it stops the inferior
+    pub temporary: bool,
+}
+
+/// A hardware watchpoint, which traps after the inferior writes to the watched bytes. x86-64 has
+/// four debug registers, so at most four watchpoints can exist at a time.
+#[derive(Debug, Clone)]
+pub struct Watchpoint {
+    pub id: usize,
+    /// Debug register slot (0-3)
+    pub slot: usize,
+    pub addr: usize,
+    pub len: usize,
+    /// What the user asked to watch (a variable name or `*addr`)
+    pub expression: String,
+    /// The watched variable's type, if a variable is being watched
+    pub entity_type: Option<Type>,
+    pub enabled: bool,
+    pub hit_count: usize,
+    /// The watched bytes as of the last time we looked, and their value before the last change
+    pub value: Vec<u8>,
+    pub old_value: Vec<u8>,
+}
+
+impl Watchpoint {
+    pub fn format_value(&self, bytes: &[u8]) -> String {
+        match &self.entity_type {
+            Some(entity_type) => entity_type.format_value(bytes),
+            None => Type::base("unsigned long", self.len, Encoding::Unsigned).format_value(bytes),
+        }
+    }
+}
+
+/// A syscall catchpoint, which stops the inferior when it enters or returns from one of the given
+/// system calls.
+#[derive(Debug, Clone)]
+pub struct Catchpoint {
+    pub id: usize,
+    /// The system call numbers to stop at; empty means any system call
+    pub syscalls: Vec<u64>,
+    pub enabled: bool,
+    pub hit_count: usize,
+}
+
+impl Catchpoint {
+    /// Returns true if this catchpoint stops at system call `number`.
+    pub fn catches(&self, number: u64) -> bool {
+        self.enabled && (self.syscalls.is_empty() || self.syscalls.contains(&number))
+    }
+}
+
+/// All of the user's breakpoints, plus the bookkeeping for which addresses are currently patched
+/// with 0xcc in the inferior. Several breakpoints may share an address; the address is patched
+/// once, and its original byte is only saved the first time.
+pub struct BreakpointTable {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    catchpoints: Vec<Catchpoint>,
+    next_id: usize,
+    /// Maps each address patched in the inferior to the instruction byte it replaced
+    inserted: HashMap<usize, u8>,
+    /// Addresses the debugger itself is running to (e.g. the return address for `finish`), which
+    /// stay patched until `remove_internal` even if no breakpoint wants them
+    internal: HashSet<usize>,
+}
+
+impl BreakpointTable {
+    pub fn new() -> BreakpointTable {
+        BreakpointTable {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            catchpoints: Vec::new(),
+            next_id: 0,
+            inserted: HashMap::new(),
+            internal: HashSet::new(),
+        }
+    }
+
+    /// Adds an enabled breakpoint and returns its ID. Call `sync` afterwards to patch the
+    /// inferior.
+    pub fn add(&mut self, addr: usize, location: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id,
+            addr,
+            location: location.to_string(),
+            enabled: true,
+            hit_count: 0,
+            condition: None,
+            ignore_count: 0,
+            temporary: false,
+        });
+        id
+    }
+
+    pub fn remove(&mut self, id: usize) -> Option<Breakpoint> {
+        let index = self.breakpoints.iter().position(|bp| bp.id == id)?;
+        Some(self.breakpoints.remove(index))
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Breakpoint> {
+        self.breakpoints.iter_mut().find(|bp| bp.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.breakpoints.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Breakpoint> {
+        self.breakpoints.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.breakpoints.is_empty() && self.watchpoints.is_empty() && self.catchpoints.is_empty()
+    }
+
+    /// Adds an enabled watchpoint in the first free debug register slot and returns its ID, or
+    /// None if all four slots are taken. Call `sync` afterwards to program the inferior.
+    pub fn add_watchpoint(
+        &mut self,
+        addr: usize,
+        len: usize,
+        expression: &str,
+        entity_type: Option<Type>,
+        value: Vec<u8>,
+    ) -> Option<usize> {
+        let slot = (0..4).find(|slot| !self.watchpoints.iter().any(|wp| wp.slot == *slot))?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id,
+            slot,
+            addr,
+            len,
+            expression: expression.to_string(),
+            entity_type,
+            enabled: true,
+            hit_count: 0,
+            old_value: value.clone(),
+            value,
+        });
+        Some(id)
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> Option<Watchpoint> {
+        let index = self.watchpoints.iter().position(|wp| wp.id == id)?;
+        Some(self.watchpoints.remove(index))
+    }
+
+    pub fn get_watchpoint_mut(&mut self, id: usize) -> Option<&mut Watchpoint> {
+        self.watchpoints.iter_mut().find(|wp| wp.id == id)
+    }
+
+    pub fn watchpoint_in_slot(&mut self, slot: usize) -> Option<&mut Watchpoint> {
+        self.watchpoints.iter_mut().find(|wp| wp.slot == slot)
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &Watchpoint> {
+        self.watchpoints.iter()
+    }
+
+    pub fn watchpoints_mut(&mut self) -> impl Iterator<Item = &mut Watchpoint> {
+        self.watchpoints.iter_mut()
+    }
+
+    /// Adds an enabled catchpoint for the system calls `syscalls` (any system call if empty) and
+    /// returns its ID. Call `sync` afterwards to start catching them.
+    pub fn add_catchpoint(&mut self, syscalls: Vec<u64>) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.catchpoints.push(Catchpoint {
+            id,
+            syscalls,
+            enabled: true,
+            hit_count: 0,
+        });
+        id
+    }
+
+    pub fn remove_catchpoint(&mut self, id: usize) -> Option<Catchpoint> {
+        let index = self.catchpoints.iter().position(|cp| cp.id == id)?;
+        Some(self.catchpoints.remove(index))
+    }
+
+    pub fn get_catchpoint_mut(&mut self, id: usize) -> Option<&mut Catchpoint> {
+        self.catchpoints.iter_mut().find(|cp| cp.id == id)
+    }
+
+    pub fn catchpoints(&self) -> impl Iterator<Item = &Catchpoint> {
+        self.catchpoints.iter()
+    }
+
+    pub fn catchpoints_mut(&mut self) -> impl Iterator<Item = &mut Catchpoint> {
+        self.catchpoints.iter_mut()
+    }
+
+    /// Returns the enabled catchpoints that stop at system call `number`.
+    pub fn catching(&mut self, number: u64) -> impl Iterator<Item = &mut Catchpoint> {
+        self.catchpoints
+            .iter_mut()
+            .filter(move |cp| cp.catches(number))
+    }
+
+    /// Returns the enabled breakpoints at `addr`.
+    pub fn enabled_at(&mut self, addr: usize) -> impl Iterator<Item = &mut Breakpoint> {
+        self.breakpoints
+            .iter_mut()
+            .filter(move |bp| bp.enabled && bp.addr == addr)
+    }
+
+    /// Returns true if `addr` is currently patched with 0xcc.
+    pub fn is_inserted(&self, addr: usize) -> bool {
+        self.inserted.contains_key(&addr)
+    }
+
+    /// Returns the original instruction byte at `addr` if it is currently patched.
+    pub fn orig_byte(&self, addr: usize) -> Option<u8> {
+        self.inserted.get(&addr).copied()
+    }
+
+    /// Patches `addr` with 0xcc unless it already is.
+    pub fn insert_at(&mut self, inferior: &mut Inferior, addr: usize) -> Result<(), nix::Error> {
+        if !self.inserted.contains_key(&addr) {
+            let orig_byte = inferior.write_byte(addr, 0xcc)?;
+            self.inserted.insert(addr, orig_byte);
+        }
+        Ok(())
+    }
+
+    /// Restores the original instruction byte at `addr` if it is patched.
+    pub fn remove_at(&mut self, inferior: &mut Inferior, addr: usize) -> Result<(), nix::Error> {
+        if let Some(orig_byte) = self.inserted.remove(&addr) {
+            inferior.write_byte(addr, orig_byte)?;
+        }
+        Ok(())
+    }
+
+    /// Patches `addr` with 0xcc for the debugger's own use, keeping it patched through `sync`
+    /// until `remove_internal` is called.
+    pub fn insert_internal(
+        &mut self,
+        inferior: &mut Inferior,
+        addr: usize,
+    ) -> Result<(), nix::Error> {
+        self.internal.insert(addr);
+        self.insert_at(inferior, addr)
+    }
+
+    /// Undoes `insert_internal`, restoring the original byte at `addr` unless an enabled
+    /// breakpoint is there too.
+    pub fn remove_internal(
+        &mut self,
+        inferior: &mut Inferior,
+        addr: usize,
+    ) -> Result<(), nix::Error> {
+        self.internal.remove(&addr);
+        if self.enabled_at(addr).next().is_none() {
+            self.remove_at(inferior, addr)?;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the debugger patched `addr` for its own use.
+    pub fn is_internal(&self, addr: usize) -> bool {
+        self.internal.contains(&addr)
+    }
+
+    /// Forgets the debugger's own patches without touching the inferior, e.g. because it exited.
+    pub fn clear_internal(&mut self) {
+        self.internal.clear();
+    }
+
+    /// Makes the patched addresses in the inferior match the enabled breakpoints: inserts 0xcc
+    /// where an enabled breakpoint or internal use is missing one and restores the original byte
+    /// where neither an enabled breakpoint nor the debugger itself needs one any more. Also
+    /// programs the debug registers for the enabled watchpoints, and has the inferior stop at
+    /// system calls if any catchpoint is enabled.
+    pub fn sync(&mut self, inferior: &mut Inferior) -> Result<(), nix::Error> {
+        let stale: Vec<usize> = self
+            .inserted
+            .keys()
+            .filter(|addr| {
+                !self.internal.contains(addr)
+                    && !self
+                        .breakpoints
+                        .iter()
+                        .any(|bp| bp.enabled && bp.addr == **addr)
+            })
+            .copied()
+            .collect();
+        for addr in stale {
+            self.remove_at(inferior, addr)?;
+        }
+        // The internal ones are planted again too, in case the memory they were in was replaced
+        let wanted: Vec<usize> = self
+            .breakpoints
+            .iter()
+            .filter(|bp| bp.enabled)
+            .map(|bp| bp.addr)
+            .chain(self.internal.iter().copied())
+            .collect();
+        for addr in wanted {
+            self.insert_at(inferior, addr)?;
+        }
+        for slot in 0..4 {
+            match self
+                .watchpoints
+                .iter()
+                .find(|wp| wp.enabled && wp.slot == slot)
+            {
+                Some(wp) => inferior.set_watchpoint(slot, wp.addr, wp.len)?,
+                None => inferior.clear_watchpoint(slot)?,
+            }
+        }
+        inferior.set_catch_syscalls(self.catchpoints.iter().any(|cp| cp.enabled));
+        Ok(())
+    }
+
+    /// Restores every patched address, disables every debug register and stops catching system
+    /// calls, leaving the inferior as if it had never been traced. The breakpoints themselves are
+    /// kept.
+    pub fn remove_all(&mut self, inferior: &mut Inferior) -> Result<(), nix::Error> {
+        let inserted: Vec<usize> = self.inserted.keys().copied().collect();
+        for addr in inserted {
+            self.remove_at(inferior, addr)?;
+        }
+        for slot in 0..4 {
+            inferior.clear_watchpoint(slot)?;
+        }
+        inferior.set_catch_syscalls(false);
+        Ok(())
+    }
+
+    /// Restores the original bytes at every patched address in `inferior`, a copy of the inferior
+    /// made by fork, while the inferior itself keeps its breakpoints.
+    pub fn restore_in(&self, inferior: &mut Inferior) -> Result<(), nix::Error> {
+        for (addr, orig_byte) in &self.inserted {
+            inferior.write_byte(*addr, *orig_byte)?;
+        }
+        Ok(())
+    }
+
+    /// Forgets which addresses are patched, e.g. because the inferior they were patched in is gone.
+    pub fn clear_inserted(&mut self) {
+        self.inserted.clear();
+    }
+}
@@ -0,0 +1,527 @@
+//! Reading ELF core files, for post-mortem debugging. A core file is an ELF file whose PT_LOAD
+//! segments hold the crashed process's memory and whose PT_NOTE segment holds everything else:
+//! the registers of each thread (NT_PRSTATUS), the command line (NT_PRPSINFO) and the files that
+//! were mapped (NT_FILE). The object crate doesn't give us the notes, so the headers are read
+//! directly. `gcore` writes core files in the same format.
+
+use crate::inferior::Mapping;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem::size_of;
+
+const ET_CORE: u16 = 4;
+const EM_X86_64: u16 = 62;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_PRPSINFO: u32 = 3;
+const NT_AUXV: u32 = 6;
+const NT_FILE: u32 = 0x4649_4c45;
+
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+const PAGE_SIZE: usize = 0x1000;
+
+/// Offsets into the x86-64 `struct elf_prstatus` and `struct elf_prpsinfo`
+const PRSTATUS_CURSIG: usize = 12;
+const PRSTATUS_PID: usize = 32;
+const PRSTATUS_REGS: usize = 112;
+const PRSTATUS_SIZE: usize = 336;
+const PRPSINFO_PID: usize = 24;
+const PRPSINFO_FNAME: usize = 40;
+const PRPSINFO_FNAME_LEN: usize = 16;
+const PRPSINFO_PSARGS: usize = 56;
+const PRPSINFO_PSARGS_LEN: usize = 80;
+const PRPSINFO_SIZE: usize = 136;
+
+/// A thread of the crashed process, as it was when the core was dumped.
+pub struct CoreThread {
+    pub tid: Pid,
+    /// The signal the thread was stopped by, if any
+    pub signal: Option<Signal>,
+    pub regs: libc::user_regs_struct,
+}
+
+/// A PT_LOAD segment: `size` bytes of memory starting at `address`, of which the first
+/// `data.len()` were dumped. Memory the kernel didn't dump (e.g. unmodified code) has to be read
+/// from the mapped file instead.
+struct Segment {
+    address: usize,
+    size: usize,
+    data: Vec<u8>,
+}
+
+pub struct CoreFile {
+    /// The command line of the crashed process
+    pub command: Option<String>,
+    /// The threads of the crashed process; the one that crashed comes first
+    pub threads: Vec<CoreThread>,
+    /// The memory mappings of the crashed process, like /proc/<pid>/maps would list them
+    pub mappings: Vec<Mapping>,
+    segments: Vec<Segment>,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Rounds `n` up to a multiple of 4, the alignment of note names and descriptors.
+fn align4(n: usize) -> usize {
+    (n + 3) & !3
+}
+
+impl CoreFile {
+    /// Reads the x86-64 core file at `path`.
+    pub fn from_file(path: &str) -> Result<CoreFile, String> {
+        let data = fs::read(path).map_err(|err| format!("{}: {}", path, err))?;
+        CoreFile::parse(&data).ok_or_else(|| format!("\"{}\" is not an x86-64 core dump.", path))
+    }
+
+    fn parse(data: &[u8]) -> Option<CoreFile> {
+        // ELF magic, 64-bit, little endian
+        if data.get(..6)? != b"\x7fELF\x02\x01"
+            || u16_at(data, 0x10)? != ET_CORE
+            || u16_at(data, 0x12)? != EM_X86_64
+        {
+            return None;
+        }
+        let phoff = u64_at(data, 0x20)? as usize;
+        let phentsize = u16_at(data, 0x36)? as usize;
+        let phnum = u16_at(data, 0x38)? as usize;
+
+        let mut core = CoreFile {
+            command: None,
+            threads: Vec::new(),
+            mappings: Vec::new(),
+            segments: Vec::new(),
+        };
+        for index in 0..phnum {
+            let header = data.get(index.checked_mul(phentsize)?.checked_add(phoff)?..)?;
+            let p_type = u32_at(header, 0)?;
+            let offset = u64_at(header, 8)? as usize;
+            let address = u64_at(header, 16)? as usize;
+            let file_size = u64_at(header, 32)? as usize;
+            let mem_size = u64_at(header, 40)? as usize;
+            let contents = data.get(offset..offset.checked_add(file_size)?)?;
+            match p_type {
+                PT_LOAD => core.segments.push(Segment {
+                    address,
+                    size: mem_size,
+                    data: contents.to_vec(),
+                }),
+                PT_NOTE => core.parse_notes(contents)?,
+                _ => (),
+            }
+        }
+        if core.threads.is_empty() {
+            return None;
+        }
+        Some(core)
+    }
+
+    fn parse_notes(&mut self, mut notes: &[u8]) -> Option<()> {
+        while notes.len() >= 12 {
+            let name_size = u32_at(notes, 0)? as usize;
+            let desc_size = u32_at(notes, 4)? as usize;
+            let note_type = u32_at(notes, 8)?;
+            let desc_start = 12 + align4(name_size);
+            let desc = notes.get(desc_start..desc_start.checked_add(desc_size)?)?;
+            match note_type {
+                NT_PRSTATUS => {
+                    let regs = desc
+                        .get(PRSTATUS_REGS..PRSTATUS_REGS + size_of::<libc::user_regs_struct>())?;
+                    let signal = u16_at(desc, PRSTATUS_CURSIG)? as i32;
+                    self.threads.push(CoreThread {
+                        tid: Pid::from_raw(u32_at(desc, PRSTATUS_PID)? as i32),
+                        signal: Signal::try_from(signal).ok(),
+                        // pr_reg is laid out exactly like user_regs_struct
+                        regs: unsafe {
+                            std::ptr::read_unaligned(regs.as_ptr() as *const libc::user_regs_struct)
+                        },
+                    });
+                }
+                NT_PRPSINFO => {
+                    let psargs =
+                        desc.get(PRPSINFO_PSARGS..PRPSINFO_PSARGS + PRPSINFO_PSARGS_LEN)?;
+                    let len = psargs.iter().position(|b| *b == 0).unwrap_or(psargs.len());
+                    let command = String::from_utf8_lossy(&psargs[..len]);
+                    self.command = Some(command.trim_end().to_string());
+                }
+                NT_FILE => self.parse_file_note(desc)?,
+                _ => (),
+            }
+            notes = notes.get(desc_start.checked_add(align4(desc_size))?..)?;
+        }
+        Some(())
+    }
+
+    /// Parses the NT_FILE note: a count and a page size, then the start, end and page offset of
+    /// each file mapping, then their NUL-terminated paths.
+    fn parse_file_note(&mut self, desc: &[u8]) -> Option<()> {
+        let count = u64_at(desc, 0)? as usize;
+        let page_size = u64_at(desc, 8)? as usize;
+        // The sizes come from the file, so they mustn't be trusted not to overflow
+        let mut paths = desc
+            .get(count.checked_mul(24)?.checked_add(16)?..)?
+            .split(|b| *b == 0);
+        for index in 0..count {
+            let entry = 16 + index * 24;
+            self.mappings.push(Mapping {
+                start: u64_at(desc, entry)? as usize,
+                end: u64_at(desc, entry + 8)? as usize,
+                // Not recorded in the note
+                permissions: String::new(),
+                offset: (u64_at(desc, entry + 16)? as usize).checked_mul(page_size)?,
+                path: Some(String::from_utf8_lossy(paths.next()?).into_owned()),
+            });
+        }
+        Some(())
+    }
+
+    /// Reads `len` bytes of the crashed process's memory at `addr`, from the core file if it was
+    /// dumped and from the mapped file otherwise. Returns None if the memory isn't available.
+    pub fn read(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|segment| segment.address <= addr && addr < segment.address + segment.size)?;
+        let offset = addr - segment.address;
+        if offset + len <= segment.data.len() {
+            return Some(segment.data[offset..offset + len].to_vec());
+        }
+        if offset + len > segment.size {
+            return None;
+        }
+        let mapping = self
+            .mappings
+            .iter()
+            .find(|mapping| mapping.start <= addr && addr + len <= mapping.end)?;
+        let mut file = fs::File::open(mapping.path.as_ref()?).ok()?;
+        file.seek(SeekFrom::Start(
+            (mapping.offset + addr - mapping.start) as u64,
+        ))
+        .ok()?;
+        let mut bytes = vec![0; len];
+        file.read_exact(&mut bytes).ok()?;
+        Some(bytes)
+    }
+}
+
+/// Appends a note to `notes`, with its name and descriptor padded to 4 bytes.
+fn push_note(notes: &mut Vec<u8>, note_type: u32, desc: &[u8]) {
+    let name = b"CORE\0";
+    notes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+    notes.extend_from_slice(&note_type.to_le_bytes());
+    notes.extend_from_slice(name);
+    notes.resize(align4(notes.len()), 0);
+    notes.extend_from_slice(desc);
+    notes.resize(align4(notes.len()), 0);
+}
+
+/// Builds the notes of a core file of process `pid`: a NT_PRSTATUS for each thread, then
+/// NT_PRPSINFO, NT_AUXV and NT_FILE.
+fn build_notes(pid: Pid, threads: &[CoreThread], mappings: &[Mapping]) -> Vec<u8> {
+    let mut notes = Vec::new();
+    for thread in threads {
+        let mut prstatus = vec![0; PRSTATUS_SIZE];
+        let signal = thread.signal.map_or(0, |signal| signal as i32);
+        prstatus[..4].copy_from_slice(&signal.to_le_bytes());
+        prstatus[PRSTATUS_CURSIG..PRSTATUS_CURSIG + 2]
+            .copy_from_slice(&(signal as u16).to_le_bytes());
+        prstatus[PRSTATUS_PID..PRSTATUS_PID + 4]
+            .copy_from_slice(&thread.tid.as_raw().to_le_bytes());
+        // pr_reg is laid out exactly like user_regs_struct
+        let regs = unsafe {
+            std::slice::from_raw_parts(
+                &thread.regs as *const libc::user_regs_struct as *const u8,
+                size_of::<libc::user_regs_struct>(),
+            )
+        };
+        prstatus[PRSTATUS_REGS..PRSTATUS_REGS + regs.len()].copy_from_slice(regs);
+        push_note(&mut notes, NT_PRSTATUS, &prstatus);
+    }
+
+    let mut prpsinfo = vec![0; PRPSINFO_SIZE];
+    prpsinfo[PRPSINFO_PID..PRPSINFO_PID + 4].copy_from_slice(&pid.as_raw().to_le_bytes());
+    let comm = fs::read_to_string(format!("/proc/{}/comm", pid)).unwrap_or_default();
+    let comm = comm.trim_end().as_bytes();
+    let len = comm.len().min(PRPSINFO_FNAME_LEN - 1);
+    prpsinfo[PRPSINFO_FNAME..PRPSINFO_FNAME + len].copy_from_slice(&comm[..len]);
+    // The command line, with the NULs between arguments turned into spaces
+    let cmdline: Vec<u8> = fs::read(format!("/proc/{}/cmdline", pid))
+        .unwrap_or_default()
+        .iter()
+        .map(|b| if *b == 0 { b' ' } else { *b })
+        .collect();
+    let len = cmdline.len().min(PRPSINFO_PSARGS_LEN - 1);
+    prpsinfo[PRPSINFO_PSARGS..PRPSINFO_PSARGS + len].copy_from_slice(&cmdline[..len]);
+    push_note(&mut notes, NT_PRPSINFO, &prpsinfo);
+
+    if let Ok(auxv) = fs::read(format!("/proc/{}/auxv", pid)) {
+        push_note(&mut notes, NT_AUXV, &auxv);
+    }
+
+    let files: Vec<&Mapping> = mappings
+        .iter()
+        .filter(|mapping| {
+            mapping
+                .path
+                .as_ref()
+                .map_or(false, |path| path.starts_with('/'))
+        })
+        .collect();
+    let mut file_note = Vec::new();
+    file_note.extend_from_slice(&(files.len() as u64).to_le_bytes());
+    file_note.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    for mapping in &files {
+        for value in &[mapping.start, mapping.end, mapping.offset / PAGE_SIZE] {
+            file_note.extend_from_slice(&(*value as u64).to_le_bytes());
+        }
+    }
+    for mapping in &files {
+        file_note.extend_from_slice(mapping.path.as_ref().unwrap().as_bytes());
+        file_note.push(0);
+    }
+    push_note(&mut notes, NT_FILE, &file_note);
+    notes
+}
+
+/// Writes a core file of the stopped process `pid` to `path`. It holds the registers of
+/// `threads` (the one to show first comes first) and the contents of every readable mapping in
+/// `mappings`, read through /proc/<pid>/mem.
+pub fn write_core(
+    path: &str,
+    pid: Pid,
+    threads: &[CoreThread],
+    mappings: &[Mapping],
+) -> io::Result<()> {
+    let notes = build_notes(pid, threads, mappings);
+    // The vsyscall page is in kernel space and can't be read
+    let mappings: Vec<&Mapping> = mappings
+        .iter()
+        .filter(|mapping| mapping.path.as_deref() != Some("[vsyscall]"))
+        .collect();
+    let mut mem = fs::File::open(format!("/proc/{}/mem", pid))?;
+    let contents: Vec<Vec<u8>> = mappings
+        .iter()
+        .map(|mapping| {
+            if !mapping.permissions.starts_with('r') {
+                return Vec::new();
+            }
+            let mut bytes = vec![0; mapping.end - mapping.start];
+            match mem
+                .seek(SeekFrom::Start(mapping.start as u64))
+                .and_then(|_| mem.read_exact(&mut bytes))
+            {
+                Ok(()) => bytes,
+                // e.g. [vvar], parts of which can't be read
+                Err(_) => Vec::new(),
+            }
+        })
+        .collect();
+
+    let phnum = mappings.len() + 1;
+    let notes_offset = ELF_HEADER_SIZE + phnum * PROGRAM_HEADER_SIZE;
+    let mut offset = (notes_offset + notes.len() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1);
+
+    let mut header = Vec::with_capacity(notes_offset);
+    header.extend_from_slice(b"\x7fELF\x02\x01\x01");
+    header.resize(16, 0);
+    header.extend_from_slice(&ET_CORE.to_le_bytes());
+    header.extend_from_slice(&EM_X86_64.to_le_bytes());
+    header.extend_from_slice(&1u32.to_le_bytes()); // e_version
+    header.extend_from_slice(&0u64.to_le_bytes()); // e_entry
+    header.extend_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes()); // e_phoff
+    header.extend_from_slice(&0u64.to_le_bytes()); // e_shoff
+    header.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+    header.extend_from_slice(&(ELF_HEADER_SIZE as u16).to_le_bytes());
+    header.extend_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    header.extend_from_slice(&(phnum as u16).to_le_bytes());
+    header.extend_from_slice(&[0; 6]); // no section headers
+
+    let mut program_header = |p_type: u32,
+                              flags: u32,
+                              offset: usize,
+                              address: usize,
+                              file_size: usize,
+                              mem_size: usize,
+                              align: usize| {
+        header.extend_from_slice(&p_type.to_le_bytes());
+        header.extend_from_slice(&flags.to_le_bytes());
+        for value in &[offset, address, 0, file_size, mem_size, align] {
+            header.extend_from_slice(&(*value as u64).to_le_bytes());
+        }
+    };
+    program_header(PT_NOTE, 0, notes_offset, 0, notes.len(), 0, 4);
+    for (mapping, bytes) in mappings.iter().zip(&contents) {
+        let permissions = mapping.permissions.as_bytes();
+        let flag = |index: usize, letter: u8, bit: u32| {
+            if permissions.get(index) == Some(&letter) {
+                bit
+            } else {
+                0
+            }
+        };
+        let flags = flag(0, b'r', 4) | flag(1, b'w', 2) | flag(2, b'x', 1);
+        program_header(
+            PT_LOAD,
+            flags,
+            offset,
+            mapping.start,
+            bytes.len(),
+            mapping.end - mapping.start,
+            PAGE_SIZE,
+        );
+        offset += bytes.len();
+    }
+
+    let mut file = io::BufWriter::new(fs::File::create(path)?);
+    file.write_all(&header)?;
+    file.write_all(&notes)?;
+    let padding = ((notes_offset + notes.len() + PAGE_SIZE - 1) & !(PAGE_SIZE - 1))
+        - (notes_offset + notes.len());
+    file.write_all(&vec![0; padding])?;
+    for bytes in &contents {
+        file.write_all(bytes)?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn empty_core() -> CoreFile {
+        CoreFile {
+            command: None,
+            threads: Vec::new(),
+            mappings: Vec::new(),
+            segments: Vec::new(),
+        }
+    }
+
+    /// An x86-64 core file header with `phnum` program headers at `phoff`.
+    fn elf_header(phoff: u64, phnum: u16) -> Vec<u8> {
+        let mut data = vec![0; 64];
+        data[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        data[0x10..0x12].copy_from_slice(&ET_CORE.to_le_bytes());
+        data[0x12..0x14].copy_from_slice(&EM_X86_64.to_le_bytes());
+        data[0x20..0x28].copy_from_slice(&phoff.to_le_bytes());
+        data[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        data[0x38..0x3a].copy_from_slice(&phnum.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn rejects_headers_out_of_range() {
+        assert!(CoreFile::parse(&elf_header(u64::MAX - 8, 2)).is_none());
+        // A PT_NOTE segment whose end is past the end of the address space
+        let mut data = elf_header(64, 1);
+        let mut header = vec![0; 56];
+        header[..4].copy_from_slice(&PT_NOTE.to_le_bytes());
+        header[8..16].copy_from_slice(&64u64.to_le_bytes());
+        header[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        data.extend_from_slice(&header);
+        assert!(CoreFile::parse(&data).is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_note() {
+        let mut notes = Vec::new();
+        for value in &[5, 100, NT_PRPSINFO] {
+            notes.extend_from_slice(&value.to_le_bytes());
+        }
+        notes.extend_from_slice(b"CORE\0\0\0\0abcd");
+        assert!(empty_core().parse_notes(&notes).is_none());
+    }
+
+    #[test]
+    fn rejects_file_note_count_overflow() {
+        let mut desc = Vec::new();
+        desc.extend_from_slice(&(u64::MAX / 8).to_le_bytes());
+        desc.extend_from_slice(&0x1000u64.to_le_bytes());
+        assert!(empty_core().parse_file_note(&desc).is_none());
+    }
+
+    fn thread(tid: i32, signal: Option<Signal>, rip: u64) -> CoreThread {
+        let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = rip;
+        CoreThread {
+            tid: Pid::from_raw(tid),
+            signal,
+            regs,
+        }
+    }
+
+    #[test]
+    fn push_note_pads_to_four_bytes() {
+        let mut notes = Vec::new();
+        push_note(&mut notes, NT_PRSTATUS, b"abcde");
+        // Header, "CORE\0" padded to 8, then the descriptor padded to 8
+        assert_eq!(notes.len(), 12 + 8 + 8);
+        assert_eq!(&notes[20..25], b"abcde");
+        assert_eq!(&notes[25..], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn notes_round_trip() {
+        let pid = Pid::this();
+        let threads = vec![
+            thread(pid.as_raw(), Some(Signal::SIGSEGV), 0x401136),
+            thread(pid.as_raw() + 1, None, 0x401200),
+        ];
+        let mappings = vec![
+            Mapping {
+                start: 0x400000,
+                end: 0x402000,
+                permissions: "r-xp".to_string(),
+                offset: 0x1000,
+                path: Some("/usr/bin/true".to_string()),
+            },
+            // Only mapped files go in NT_FILE
+            Mapping {
+                start: 0x7000_0000,
+                end: 0x7000_1000,
+                permissions: "rw-p".to_string(),
+                offset: 0,
+                path: Some("[heap]".to_string()),
+            },
+        ];
+        let mut core = empty_core();
+        core.parse_notes(&build_notes(pid, &threads, &mappings))
+            .unwrap();
+
+        assert_eq!(core.threads.len(), 2);
+        for (parsed, thread) in core.threads.iter().zip(&threads) {
+            assert_eq!(parsed.tid, thread.tid);
+            assert_eq!(parsed.signal, thread.signal);
+            assert_eq!(parsed.regs.rip, thread.regs.rip);
+        }
+        // The command line of this test process
+        assert!(!core.command.unwrap().is_empty());
+        assert_eq!(core.mappings.len(), 1);
+        let mapping = &core.mappings[0];
+        assert_eq!((mapping.start, mapping.end), (0x400000, 0x402000));
+        assert_eq!(mapping.offset, 0x1000);
+        assert_eq!(mapping.path.as_deref(), Some("/usr/bin/true"));
+    }
+}
@@ -1,16 +1,67 @@
-use crate::debugger_command::DebuggerCommand;
-use crate::dwarf_data::{DwarfData, Error as DwarfError};
-use crate::inferior::Inferior;
+use crate::breakpoint::BreakpointTable;
+use crate::core_file::CoreFile;
+use crate::debugger_command::{DebuggerCommand, FollowForkMode};
+use crate::disassemble::{disassemble, instruction_effects, DecodedInstruction, Flow};
+use crate::dwarf_data::{
+    DwarfData, Encoding, Error as DwarfError, Function, Line, Pointee, Type, TypeKind, Variable,
+};
+use crate::expression::{self, evaluate, integer_type, Expression, Scope, Value};
+use crate::inferior::{print_frame, Event, Inferior, Status, REGISTER_NAMES};
+use crate::location::{self, expression_value, read_location, OPTIMIZED_OUT};
+use crate::record::{Entry, Recording, DEFAULT_LIMIT};
+use crate::signal_policy::{parse_signal, SignalTable};
+use crate::source::SourceFiles;
+use crate::syscall::{
+    argument_count, path_arguments, syscall_name, syscall_number, ARGUMENT_REGISTERS,
+};
+use crate::unwind::{Registers, Unwind, CALLEE_SAVED};
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
+/// x86-64 instructions are at most this many bytes long
+const MAX_INSTRUCTION_LEN: usize = 15;
+
+/// How many lines `list` shows
+const LIST_SIZE: usize = 10;
+
+/// How many instructions `disassemble` shows when it doesn't know where the function ends
+const DISASSEMBLE_COUNT: usize = 16;
+
+/// An expression `display` prints every time the inferior stops.
+struct AutoDisplay {
+    id: usize,
+    expression: String,
+}
+
 pub struct Debugger {
     target: String,
     history_path: String,
     readline: Editor<()>,
     inferior: Option<Inferior>,
     debug_data: DwarfData,
-    breakpoints: Vec<(usize, u8)>,
+    breakpoints: BreakpointTable,
+    /// The inferior's stack as of the last time it was unwound, innermost frame first. Cleared
+    /// whenever the inferior runs.
+    frames: Vec<Registers>,
+    /// Index into `frames` of the frame `print` and friends look at
+    selected_frame: usize,
+    sources: SourceFiles,
+    /// The file and line a plain `list` continues from; None to list around the selected frame
+    list_position: Option<(String, usize)>,
+    signals: SignalTable,
+    /// The signal the inferior is stopped with, if it stopped because of one
+    stop_signal: Option<Signal>,
+    /// The signal to deliver when the inferior is next resumed
+    pending_signal: Option<Signal>,
+    /// Which process to keep debugging when the inferior forks
+    follow_fork_mode: FollowForkMode,
+    /// The execution log, while `record` is on
+    recording: Option<Recording>,
+    displays: Vec<AutoDisplay>,
+    next_display_id: usize,
 }
 
 impl Debugger {
@@ -33,7 +84,7 @@ impl Debugger {
         // Attempt to load history from ~/.deet_history if it exists
         let _ = readline.load_history(&history_path);
 
-        let breakpoints = Vec::new();
+        let breakpoints = BreakpointTable::new();
 
         debug_data.print();
 
@@ -44,99 +95,506 @@ impl Debugger {
             inferior: None,
             debug_data,
             breakpoints,
+            frames: Vec::new(),
+            selected_frame: 0,
+            sources: SourceFiles::new(),
+            list_position: None,
+            signals: SignalTable::new(),
+            stop_signal: None,
+            pending_signal: None,
+            follow_fork_mode: FollowForkMode::Parent,
+            recording: None,
+            displays: Vec::new(),
+            next_display_id: 1,
         }
     }
 
     pub fn run(&mut self) {
         loop {
-            match self.get_next_command() {
+            let command = self.get_next_command();
+            if command.needs_process() && self.inferior.as_ref().map_or(false, Inferior::is_core) {
+                println!("The program is not being run.");
+                continue;
+            }
+            match command {
                 DebuggerCommand::Run(args) => {
-                    if self.inferior.is_some() {
-                        self.inferior.as_mut().unwrap().kill();
-                        self.inferior = None;
-                    }
+                    self.release_inferior();
 
                     if let Some(inferior) = Inferior::new(&self.target, &args) {
                         // Create the inferior
                         self.inferior = Some(inferior);
+                        self.pending_signal = None;
+                        self.forget_frames();
 
-                        self.update_breakpoint();
+                        self.breakpoints.clear_inserted();
+                        if let Err(err) = self.breakpoints.sync(self.inferior.as_mut().unwrap()) {
+                            println!("Warning: failed to insert breakpoints: {}", err);
+                        }
+                        self.refresh_watchpoints();
 
-                        let status = self.inferior.as_mut().unwrap().continue_inferior().unwrap();
-                        self.inferior
-                            .as_mut()
-                            .unwrap()
-                            .print(&status, &self.debug_data);
+                        match self.continue_inferior() {
+                            Ok(status) => self.print_status(status),
+                            Err(err) => println!("Error continuing inferior: {}", err),
+                        }
                     } else {
                         println!("Error starting subprocess");
                     }
                 }
-                DebuggerCommand::Quit => {
-                    if self.inferior.is_some() {
-                        self.inferior.as_mut().unwrap().kill();
+                DebuggerCommand::Attach(pid) => {
+                    self.release_inferior();
+                    self.attach(Pid::from_raw(pid));
+                }
+                DebuggerCommand::Detach => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
                     }
+                    let pid = self.inferior.as_ref().unwrap().pid();
+                    match self.detach() {
+                        Ok(()) => println!("Detaching from process {}", pid),
+                        Err(err) => println!("Error detaching from process {}: {}", pid, err),
+                    }
+                }
+                DebuggerCommand::Quit => {
+                    self.release_inferior();
                     return;
                 }
-                DebuggerCommand::Cont => match self.inferior.as_mut() {
-                    None => {
+                DebuggerCommand::Cont => {
+                    if self.inferior.is_none() {
                         println!("Error: can't use cont when no process running!");
+                        continue;
                     }
-                    Some(inferior) => {
-                        let rip = inferior.rip();
-                        match self
-                            .breakpoints
-                            .iter()
-                            .find(|(addr, _val)| rip - 1 == *addr)
-                        {
-                            Some((addr, val)) => {
-                                inferior.write_byte(*addr, *val).expect("0xcc -> val error");
-                                inferior.back_rip().unwrap();
-                                inferior.step().unwrap();
-                                inferior.write_byte(*addr, 0xcc).expect("val -> 0xcc error");
+                    match self.continue_inferior() {
+                        Ok(status) => self.print_status(status),
+                        Err(err) => println!("Error continuing inferior: {}", err),
+                    }
+                }
+                DebuggerCommand::Signal(name) => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
+                    }
+                    if name == "0" {
+                        self.pending_signal = None;
+                        println!("Continuing with no signal.");
+                    } else {
+                        match parse_signal(&name) {
+                            Some(signal) => {
+                                self.pending_signal = Some(signal);
+                                println!("Continuing with signal {}.", signal);
                             }
-                            _ => (),
-                        }
-
-                        let status = inferior.continue_inferior().unwrap();
-                        inferior.print(&status, &self.debug_data);
-
-                        match status {
-                            crate::inferior::Status::Exited(_) => {
-                                self.inferior = None;
+                            None => {
+                                println!("Bad signal \"{}\".", name);
+                                continue;
                             }
-                            _ => (),
                         }
                     }
+                    match self.continue_inferior() {
+                        Ok(status) => self.print_status(status),
+                        Err(err) => println!("Error continuing inferior: {}", err),
+                    }
+                }
+                DebuggerCommand::Handle(name, keywords) => {
+                    let signal = match parse_signal(&name) {
+                        Some(signal) => signal,
+                        None => {
+                            println!("Unrecognized or ambiguous signal \"{}\".", name);
+                            continue;
+                        }
+                    };
+                    if let Err(keyword) = self.signals.update(signal, &keywords) {
+                        println!("Unrecognized flag or signal name \"{}\".", keyword);
+                        continue;
+                    }
+                    // The new setting decides whether the signal we are stopped with gets passed
+                    if self.stop_signal == Some(signal) {
+                        self.pending_signal = if self.signals.get(signal).pass {
+                            Some(signal)
+                        } else {
+                            None
+                        };
+                    }
+                    self.signals.print(&[signal]);
+                }
+                DebuggerCommand::InfoSignals(name) => match name {
+                    Some(name) => match parse_signal(&name) {
+                        Some(signal) => self.signals.print(&[signal]),
+                        None => println!("Unrecognized or ambiguous signal \"{}\".", name),
+                    },
+                    None => {
+                        let all: Vec<Signal> = Signal::iterator().collect();
+                        self.signals.print(&all);
+                    }
+                },
+                DebuggerCommand::Record => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
+                    }
+                    if self.recording.is_some() {
+                        println!(
+                            "The process is already being recorded. Use \"record stop\" to stop \
+                             recording first."
+                        );
+                        continue;
+                    }
+                    self.recording = Some(Recording::new(DEFAULT_LIMIT));
+                }
+                DebuggerCommand::RecordStop => match self.recording.take() {
+                    Some(_) => {
+                        println!("Process record is stopped and all execution logs are deleted.")
+                    }
+                    None => println!("No recording is currently active."),
+                },
+                DebuggerCommand::InfoRecord => match &self.recording {
+                    Some(recording) => {
+                        println!("Log contains {} instructions.", recording.len());
+                        println!("Max logged instructions is {}.", recording.limit());
+                    }
+                    None => println!("No recording is currently active."),
                 },
+                DebuggerCommand::ReverseStepi => {
+                    self.reverse_command("reverse-stepi", Debugger::reverse_stepi)
+                }
+                DebuggerCommand::ReverseNext => {
+                    self.reverse_command("reverse-next", Debugger::reverse_next)
+                }
+                DebuggerCommand::ReverseContinue => {
+                    self.reverse_command("reverse-continue", Debugger::reverse_continue)
+                }
+                DebuggerCommand::Step => self.step_command("step", true),
+                DebuggerCommand::Next => self.step_command("next", false),
+                DebuggerCommand::Finish => {
+                    if self.inferior.is_none() {
+                        println!("Error: can't use finish when no process running!");
+                        continue;
+                    }
+                    let rip = match self.inferior.as_ref().unwrap().rip() {
+                        Ok(rip) => rip,
+                        Err(err) => {
+                            println!("Error reading registers: {}", err);
+                            continue;
+                        }
+                    };
+                    let func = self.debug_data.get_function_from_addr(rip);
+                    if func.as_deref() == Some("main") {
+                        println!("\"finish\" not meaningful in the outermost frame.");
+                        continue;
+                    }
+                    println!(
+                        "Run till exit from {}",
+                        func.unwrap_or_else(|| format!("{:#x}", rip))
+                    );
+                    match self.finish() {
+                        Ok(status) => self.print_step_status(status),
+                        Err(err) => println!("Error finishing function: {}", err),
+                    }
+                }
+                DebuggerCommand::Until(location) => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
+                    }
+                    match self.until(location.as_deref(), false) {
+                        Ok(Some(status)) => self.print_step_status(status),
+                        Ok(None) => (),
+                        Err(err) => println!("Error running inferior: {}", err),
+                    }
+                }
+                DebuggerCommand::Advance(location) => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
+                    }
+                    match self.until(Some(&location), true) {
+                        Ok(Some(status)) => self.print_step_status(status),
+                        Ok(None) => (),
+                        Err(err) => println!("Error running inferior: {}", err),
+                    }
+                }
                 DebuggerCommand::Back => {
-                    self.inferior
-                        .as_mut()
-                        .unwrap()
-                        .print_backtrace(&self.debug_data)
-                        .unwrap();
-                }
-                DebuggerCommand::Break(address) => {
-                    let addr = parse_address(&address, &self.debug_data).unwrap();
-
-                    if self.inferior.is_some() {
-                        let ori_ins = self
-                            .inferior
-                            .as_mut()
+                    if self.inferior.is_none() {
+                        println!("No stack.");
+                        continue;
+                    }
+                    if self.unwind_frames() {
+                        self.inferior
+                            .as_ref()
                             .unwrap()
-                            .write_byte(addr, 0xcc)
-                            .expect("invalid address");
-
-                        self.breakpoints.push((addr, ori_ins));
-                    } else {
-                        self.breakpoints.push((addr, 0));
+                            .print_backtrace(&self.frames, &self.debug_data);
                     }
-
-                    println!(
-                        "Set breakpoint {} at {:#x}",
-                        self.breakpoints.len() - 1,
-                        addr
+                }
+                DebuggerCommand::Up(count) => {
+                    if self.inferior.is_none() {
+                        println!("No stack.");
+                        continue;
+                    }
+                    if !self.unwind_frames() {
+                        continue;
+                    }
+                    if self.selected_frame + 1 >= self.frames.len() {
+                        println!("Initial frame selected; you cannot go up.");
+                        continue;
+                    }
+                    self.select_frame(
+                        self.selected_frame
+                            .saturating_add(count)
+                            .min(self.frames.len() - 1),
                     );
                 }
+                DebuggerCommand::Down(count) => {
+                    if self.inferior.is_none() {
+                        println!("No stack.");
+                        continue;
+                    }
+                    if !self.unwind_frames() {
+                        continue;
+                    }
+                    if self.selected_frame == 0 {
+                        println!("Bottom (innermost) frame selected; you cannot go down.");
+                        continue;
+                    }
+                    self.select_frame(self.selected_frame.saturating_sub(count));
+                }
+                DebuggerCommand::Frame(index) => {
+                    if self.inferior.is_none() {
+                        println!("No stack.");
+                        continue;
+                    }
+                    if !self.unwind_frames() {
+                        continue;
+                    }
+                    let index = index.unwrap_or(self.selected_frame);
+                    if index >= self.frames.len() {
+                        println!("No frame at level {}.", index);
+                        continue;
+                    }
+                    self.select_frame(index);
+                }
+                DebuggerCommand::InfoThreads => {
+                    if self.inferior.is_none() {
+                        println!("No threads.");
+                        continue;
+                    }
+                    self.print_threads();
+                }
+                DebuggerCommand::Gcore(file) => {
+                    let inferior = match self.inferior.as_mut() {
+                        Some(inferior) => inferior,
+                        None => {
+                            println!("The program is not being run.");
+                            continue;
+                        }
+                    };
+                    let path = file.unwrap_or_else(|| format!("core.{}", inferior.pid()));
+                    // The core should show the program's own code, not our breakpoints
+                    if let Err(err) = self.breakpoints.remove_all(inferior) {
+                        println!("Warning: failed to remove breakpoints: {}", err);
+                    }
+                    match inferior.write_core(&path, self.stop_signal) {
+                        Ok(()) => println!("Saved corefile {}", path),
+                        Err(err) => println!("Can't create a corefile: {}", err),
+                    }
+                    self.sync_breakpoints();
+                }
+                DebuggerCommand::Thread(id) => {
+                    if self.inferior.is_none() {
+                        println!("No thread selected.");
+                        continue;
+                    }
+                    let inferior = self.inferior.as_mut().unwrap();
+                    match id {
+                        Some(id) => {
+                            if !inferior.select_thread(id) {
+                                println!("Invalid thread ID: {}", id);
+                                continue;
+                            }
+                            println!(
+                                "[Switching to thread {} (LWP {})]",
+                                id,
+                                inferior.current_thread().tid
+                            );
+                            self.forget_frames();
+                        }
+                        None => {
+                            let thread = inferior.current_thread();
+                            println!("[Current thread is {} (LWP {})]", thread.id, thread.tid);
+                        }
+                    }
+                    if self.unwind_frames() {
+                        self.select_frame(self.selected_frame);
+                        match self.selected_pc() {
+                            Ok(pc) => self.print_stop_line(pc),
+                            Err(err) => println!("Error reading registers: {}", err),
+                        }
+                    }
+                }
+                DebuggerCommand::SetFollowForkMode(mode) => self.follow_fork_mode = mode,
+                DebuggerCommand::InfoRegisters(names) => {
+                    if self.inferior.is_none() {
+                        println!("The program has no registers now.");
+                        continue;
+                    }
+                    self.print_registers(&names);
+                }
+                DebuggerCommand::SetRegister(name, value) => {
+                    if self.inferior.is_none() {
+                        println!("The program has no registers now.");
+                        continue;
+                    }
+                    let value = match self
+                        .evaluate_expression(&value)
+                        .and_then(|value| value.as_address())
+                    {
+                        Ok(value) => value as u64,
+                        Err(err) => {
+                            println!("{}", err);
+                            continue;
+                        }
+                    };
+                    match self.inferior.as_ref().unwrap().set_register(&name, value) {
+                        Ok(true) => self.forget_frames(),
+                        Ok(false) => println!("Invalid register `{}'", name),
+                        Err(err) => println!("Error setting register {}: {}", name, err),
+                    }
+                }
+                DebuggerCommand::SetVariable(assignment) => {
+                    if self.inferior.is_none() {
+                        println!("The program is not being run.");
+                        continue;
+                    }
+                    self.set_variable(&assignment);
+                }
+                DebuggerCommand::Examine(count, format, size, address) => {
+                    if self.inferior.is_none() {
+                        println!("Error: can't use x when no process running!");
+                        continue;
+                    }
+                    match self.examine_address(&address) {
+                        Ok(addr) => self.examine(count, format, size, addr),
+                        Err(err) => println!("{}", err),
+                    }
+                }
+                DebuggerCommand::Disassemble(location) => {
+                    if self.inferior.is_none() {
+                        println!("Error: can't use disassemble when no process running!");
+                        continue;
+                    }
+                    self.disassemble(location.as_deref());
+                }
+                DebuggerCommand::List(location) => self.list(location.as_deref()),
+                DebuggerCommand::Directory(directories) => {
+                    if directories.is_empty() {
+                        self.sources.clear_directories();
+                    }
+                    for directory in &directories {
+                        self.sources.add_directory(directory);
+                    }
+                    let searched: Vec<String> = self
+                        .sources
+                        .directories()
+                        .iter()
+                        .map(|directory| directory.display().to_string())
+                        .collect();
+                    println!("Source directories searched: {}", searched.join(":"));
+                }
+                DebuggerCommand::InfoLocals => {
+                    if self.inferior.is_none() {
+                        println!("No frame selected.");
+                        continue;
+                    }
+                    self.print_frame_variables(false);
+                }
+                DebuggerCommand::InfoArgs => {
+                    if self.inferior.is_none() {
+                        println!("No frame selected.");
+                        continue;
+                    }
+                    self.print_frame_variables(true);
+                }
+                DebuggerCommand::Print(name) => {
+                    if self.inferior.is_none() {
+                        println!("Error: can't use print when no process running!");
+                        continue;
+                    }
+                    self.print_expression(&name);
+                }
+                DebuggerCommand::Display(expression) => match expression {
+                    Some(expression) => self.add_display(&expression),
+                    None => self.print_displays(),
+                },
+                DebuggerCommand::Undisplay(ids) => {
+                    if ids.is_empty() {
+                        self.displays.clear();
+                    }
+                    for id in ids {
+                        match self.displays.iter().position(|display| display.id == id) {
+                            Some(index) => {
+                                self.displays.remove(index);
+                            }
+                            None => println!("No display number {}.", id),
+                        }
+                    }
+                }
+                DebuggerCommand::InfoDisplay => self.print_display_list(),
+                DebuggerCommand::Break(location, condition) => {
+                    self.add_breakpoint(&location, condition, false)
+                }
+                DebuggerCommand::Tbreak(location, condition) => {
+                    self.add_breakpoint(&location, condition, true)
+                }
+                DebuggerCommand::Watch(expression) => {
+                    if self.inferior.is_none() {
+                        println!("Error: can't use watch when no process running!");
+                        continue;
+                    }
+                    self.add_watchpoint(&expression);
+                }
+                DebuggerCommand::CatchSyscall(syscalls) => self.add_catchpoint(&syscalls),
+                DebuggerCommand::InfoBreakpoints => self.print_breakpoints(),
+                DebuggerCommand::Delete(ids) => {
+                    if ids.is_empty() {
+                        let all: Vec<usize> = self.breakpoints.iter().map(|bp| bp.id).collect();
+                        for id in all {
+                            self.breakpoints.remove(id);
+                        }
+                        let all: Vec<usize> =
+                            self.breakpoints.watchpoints().map(|wp| wp.id).collect();
+                        for id in all {
+                            self.breakpoints.remove_watchpoint(id);
+                        }
+                        let all: Vec<usize> =
+                            self.breakpoints.catchpoints().map(|cp| cp.id).collect();
+                        for id in all {
+                            self.breakpoints.remove_catchpoint(id);
+                        }
+                    }
+                    for id in ids {
+                        if self.breakpoints.remove(id).is_none()
+                            && self.breakpoints.remove_watchpoint(id).is_none()
+                            && self.breakpoints.remove_catchpoint(id).is_none()
+                        {
+                            println!("No breakpoint number {}.", id);
+                        }
+                    }
+                    self.sync_breakpoints();
+                }
+                DebuggerCommand::Ignore(id, count) => match self.breakpoints.get_mut(id) {
+                    Some(bp) => {
+                        bp.ignore_count = count;
+                        match count {
+                            0 => println!("Will stop next time breakpoint {} is reached.", id),
+                            1 => println!("Will ignore next crossing of breakpoint {}.", id),
+                            _ => println!(
+                                "Will ignore next {} crossings of breakpoint {}.",
+                                count, id
+                            ),
+                        }
+                    }
+                    None => println!("No breakpoint number {}.", id),
+                },
+                DebuggerCommand::Enable(ids) => self.set_breakpoints_enabled(&ids, true),
+                DebuggerCommand::Disable(ids) => self.set_breakpoints_enabled(&ids, false),
             }
         }
     }
@@ -182,40 +640,2241 @@ impl Debugger {
         }
     }
 
-    fn update_breakpoint(&mut self) {
-        let mut new_breaks = Vec::new();
-        if !self.breakpoints.is_empty() {
-            for (addr, _) in self.breakpoints.clone() {
-                let ori_ins = self
-                    .inferior
-                    .as_mut()
+    /// Handles `step` and `next`, which only differ in whether calls are stepped into.
+    fn step_command(&mut self, name: &str, into: bool) {
+        if self.inferior.is_none() {
+            println!("Error: can't use {} when no process running!", name);
+            return;
+        }
+        match self.step_line(into) {
+            Ok(status) => self.print_step_status(status),
+            Err(err) => println!("Error stepping inferior: {}", err),
+        }
+    }
+
+    /// Runs one of the reverse execution commands, which return None once they run out of
+    /// recorded history.
+    fn reverse_command(
+        &mut self,
+        name: &str,
+        command: fn(&mut Debugger) -> Result<Option<Status>, nix::Error>,
+    ) {
+        if self.inferior.is_none() {
+            println!("The program is not being run.");
+            return;
+        }
+        if self.recording.is_none() {
+            println!(
+                "Can't use {} without a recording; use \"record\" first.",
+                name
+            );
+            return;
+        }
+        self.forget_frames();
+        self.stop_signal = None;
+        self.pending_signal = None;
+        match command(self) {
+            Ok(Some(status)) => self.print_step_status(status),
+            Ok(None) => {
+                println!("\nNo more reverse-execution history.");
+                match self.inferior.as_ref().unwrap().rip() {
+                    Ok(rip) => self.print_step_status(Status::Stopped(Signal::SIGTRAP, rip)),
+                    Err(err) => println!("Error reading registers: {}", err),
+                }
+            }
+            Err(err) => println!("Error running inferior backwards: {}", err),
+        }
+    }
+
+    /// Attaches to the running process `pid`, which must be running the executable we loaded debug
+    /// symbols from, and inserts the breakpoints into it.
+    pub fn attach(&mut self, pid: Pid) {
+        let exe = std::fs::read_link(format!("/proc/{}/exe", pid));
+        let target = std::fs::canonicalize(&self.target);
+        match (exe, target) {
+            (Ok(exe), Ok(target)) if exe == target => (),
+            (Ok(exe), _) => {
+                println!(
+                    "Error: process {} is running {}, not {}",
+                    pid,
+                    exe.display(),
+                    self.target
+                );
+                return;
+            }
+            (Err(err), _) => {
+                println!(
+                    "Error: can't find the executable of process {}: {}",
+                    pid, err
+                );
+                return;
+            }
+        }
+
+        match Inferior::attach(pid) {
+            Ok(inferior) => {
+                println!("Attaching to process {}", pid);
+                self.inferior = Some(inferior);
+                self.pending_signal = None;
+                self.forget_frames();
+                self.breakpoints.clear_inserted();
+                if let Err(err) = self.breakpoints.sync(self.inferior.as_mut().unwrap()) {
+                    println!("Warning: failed to insert breakpoints: {}", err);
+                }
+                self.refresh_watchpoints();
+                self.print_stop_location();
+            }
+            Err(err) => println!("Error attaching to process {}: {}", pid, err),
+        }
+    }
+
+    /// Loads the core file at `path` for post-mortem debugging, as if the crashed process were the
+    /// inferior. It can be examined but not run.
+    pub fn load_core(&mut self, path: &str) {
+        let core = match CoreFile::from_file(path) {
+            Ok(core) => core,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        if let Some(command) = &core.command {
+            println!("Core was generated by `{}'.", command);
+        }
+        if let Some(signal) = core.threads[0].signal {
+            println!("Program terminated with signal {}.", signal);
+        }
+        self.release_inferior();
+        self.inferior = Some(Inferior::from_core(core));
+        self.forget_frames();
+        self.print_stop_location();
+    }
+
+    /// Says where the inferior is stopped, after attaching to it or loading a core file.
+    fn print_stop_location(&mut self) {
+        let rip = match self.inferior.as_ref().unwrap().rip() {
+            Ok(rip) => rip,
+            Err(err) => {
+                println!("Error reading registers: {}", err);
+                return;
+            }
+        };
+        self.inferior
+            .as_ref()
+            .unwrap()
+            .print_location(rip, &self.debug_data);
+        self.print_stop_line(rip);
+        self.print_displays();
+    }
+
+    /// Removes every breakpoint and watchpoint from the inferior and lets it run on untraced.
+    fn detach(&mut self) -> Result<(), nix::Error> {
+        let mut inferior = self.inferior.take().unwrap();
+        self.breakpoints.remove_all(&mut inferior)?;
+        inferior.detach()
+    }
+
+    /// Gets rid of the current inferior, if any: a process we attached to is detached from and
+    /// left running, one we spawned is killed.
+    fn release_inferior(&mut self) {
+        // There is nothing to kill or detach from for a core file
+        if self.inferior.as_ref().map_or(false, Inferior::is_core) {
+            self.inferior = None;
+        }
+        match self
+            .inferior
+            .as_ref()
+            .map(|inferior| inferior.is_attached())
+        {
+            Some(true) => {
+                let pid = self.inferior.as_ref().unwrap().pid();
+                if let Err(err) = self.detach() {
+                    println!("Error detaching from process {}: {}", pid, err);
+                }
+            }
+            Some(false) => {
+                self.inferior.as_mut().unwrap().kill();
+                self.inferior = None;
+            }
+            None => (),
+        }
+        self.recording = None;
+        self.stop_signal = None;
+        self.pending_signal = None;
+    }
+
+    /// Prints a status returned by the inferior, forgetting the inferior once it is gone.
+    fn print_status(&mut self, status: Status) {
+        if let Status::Watchpoint(slot, _) = status {
+            if let Some(wp) = self.breakpoints.watchpoint_in_slot(slot) {
+                println!("\nHardware watchpoint {}: {}\n", wp.id, wp.expression);
+                println!("Old value = {}", wp.format_value(&wp.old_value));
+                println!("New value = {}", wp.format_value(&wp.value));
+            }
+        }
+        if let Status::Syscall(number, ret, _) = status {
+            self.print_syscall(number, ret);
+        }
+        self.inferior
+            .as_ref()
+            .unwrap()
+            .print(&status, &self.debug_data);
+        match status {
+            Status::Exited(_) | Status::Signaled(_) => {
+                self.inferior = None;
+                self.recording = None;
+            }
+            Status::Stopped(_, rip)
+            | Status::Watchpoint(_, rip)
+            | Status::Event(_, rip)
+            | Status::Syscall(_, _, rip) => {
+                self.print_stop_line(rip);
+                self.print_displays();
+            }
+        }
+    }
+
+    /// Like print_status, but a plain SIGTRAP (the normal outcome of a step) only reports the new
+    /// position instead of a "Child stop" message.
+    fn print_step_status(&mut self, status: Status) {
+        match status {
+            Status::Stopped(Signal::SIGTRAP, rip) => {
+                self.inferior
+                    .as_ref()
                     .unwrap()
-                    .write_byte(addr, 0xcc)
-                    .expect("invalid address");
-                new_breaks.push((addr, ori_ins));
+                    .print_location(rip, &self.debug_data);
+                self.print_stop_line(rip);
+                self.print_displays();
+            }
+            _ => self.print_status(status),
+        }
+    }
+
+    /// If the inferior is sitting on one of our breakpoints, puts the original instruction back,
+    /// single-steps over it (delivering `signal`, if any) and re-inserts the 0xcc. Returns the
+    /// status of the step, or None if there was no breakpoint to step over, in which case `signal`
+    /// hasn't been delivered.
+    fn step_over_breakpoint(
+        &mut self,
+        signal: Option<Signal>,
+    ) -> Result<Option<Status>, nix::Error> {
+        let rip = self.inferior.as_ref().unwrap().rip()?;
+        match self.breakpoints.orig_byte(rip) {
+            Some(orig_byte) => {
+                self.inferior.as_mut().unwrap().write_byte(rip, orig_byte)?;
+                let status = self.step_inferior(signal)?;
+                match status {
+                    // After exec the old code is gone
+                    Status::Exited(_) | Status::Signaled(_) | Status::Event(Event::Exec, _) => (),
+                    _ => {
+                        self.inferior.as_mut().unwrap().write_byte(rip, 0xcc)?;
+                    }
+                }
+                Ok(Some(status))
             }
+            None => Ok(None),
+        }
+    }
 
-            self.breakpoints = new_breaks;
+    /// After the inferior traps on one of our breakpoints, rip points one byte past the 0xcc.
+    /// Rewinds it so that it points at the breakpoint address again.
+    fn rewind_breakpoint(&mut self, status: Status) -> Result<Status, nix::Error> {
+        if let Status::Stopped(Signal::SIGTRAP, rip) = status {
+            let addr = rip.wrapping_sub(1);
+            if self.breakpoints.is_inserted(addr) {
+                self.inferior.as_mut().unwrap().back_rip()?;
+                return Ok(Status::Stopped(Signal::SIGTRAP, addr));
+            }
         }
+        Ok(status)
+    }
+
+    /// Resumes the inferior until it stops again, stepping over the breakpoint it is sitting on
+    /// (if any) and delivering the pending signal. Breakpoints whose condition is false or that are
+    /// being ignored, watchpoints whose value didn't change, and signals that are set to `nostop`
+    /// don't count as a stop, and neither do system calls no enabled catchpoint is for; the
+    /// inferior is silently resumed past them.
+    fn continue_inferior(&mut self) -> Result<Status, nix::Error> {
+        self.forget_frames();
+        self.stop_signal = None;
+        loop {
+            let mut signal = self.pending_signal.take();
+            if let Some(status) = self.step_over_breakpoint(signal)? {
+                signal = None;
+                match status {
+                    Status::Stopped(Signal::SIGTRAP, _) => (),
+                    Status::Stopped(signal, _) if !self.signal_stops(signal) => continue,
+                    Status::Watchpoint(slot, _) if !self.watchpoint_changed(slot) => (),
+                    Status::Event(event, _) => {
+                        self.handle_event(event)?;
+                        continue;
+                    }
+                    _ => return Ok(status),
+                }
+            }
+            let status = match self.recording {
+                Some(_) => self.record_until_stop(signal)?,
+                None => {
+                    let status = self.inferior.as_mut().unwrap().continue_inferior(signal)?;
+                    self.rewind_breakpoint(status)?
+                }
+            };
+            match status {
+                Status::Stopped(Signal::SIGTRAP, rip)
+                    if self.breakpoints.is_inserted(rip) && !self.breakpoint_hit(rip) =>
+                {
+                    continue
+                }
+                status @ Status::Stopped(Signal::SIGTRAP, _) => return Ok(status),
+                Status::Stopped(signal, _) if !self.signal_stops(signal) => continue,
+                Status::Watchpoint(slot, _) if !self.watchpoint_changed(slot) => continue,
+                Status::Syscall(number, _, _) if !self.syscall_caught(number) => continue,
+                Status::Event(event, _) => self.handle_event(event)?,
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// Executes a single instruction, logging what it changes if we are recording.
+    fn step_inferior(&mut self, signal: Option<Signal>) -> Result<Status, nix::Error> {
+        let entry = match self.recording {
+            Some(_) => Some(self.record_entry()?),
+            None => None,
+        };
+        let status = self.inferior.as_mut().unwrap().step(signal)?;
+        match (entry, &status) {
+            (_, Status::Exited(_)) | (_, Status::Signaled(_)) => (),
+            (Some(entry), _) => {
+                if let Some(recording) = self.recording.as_mut() {
+                    // We don't know what memory the kernel wrote, so there is no going back past a
+                    // system call
+                    if entry.flow == Flow::Syscall {
+                        recording.clear();
+                    } else {
+                        recording.push(entry);
+                    }
+                }
+            }
+            (None, _) => (),
+        }
+        Ok(status)
     }
-}
 
-fn parse_address(addr: &str, dwarfdata: &DwarfData) -> Option<usize> {
-    match addr.parse::<usize>() {
-        Ok(line_number) => return dwarfdata.get_addr_for_line(None, line_number),
-        _ => (),
+    /// Saves the registers, and the memory the instruction at rip is about to write, so that the
+    /// instruction can be undone.
+    fn record_entry(&self) -> Result<Entry, nix::Error> {
+        let regs = self.inferior.as_ref().unwrap().registers()?;
+        let rip = regs.rip as usize;
+        // The instruction may end right before an unmapped page
+        let code = self
+            .read_code(rip, MAX_INSTRUCTION_LEN)
+            .or_else(|_| self.read_code(rip, (rip | 0xfff) + 1 - rip))?;
+        let (flow, writes) = match instruction_effects(&code, &regs) {
+            Some(effects) => (effects.flow, effects.writes),
+            None => (Flow::Other, Vec::new()),
+        };
+        // Memory we can't read can't be written either; the instruction will fault instead
+        let memory = writes
+            .into_iter()
+            .filter_map(|(addr, len)| {
+                let bytes = self.inferior.as_ref().unwrap().read_bytes(addr, len).ok()?;
+                Some((addr, bytes))
+            })
+            .collect();
+        Ok(Entry { regs, memory, flow })
     }
 
-    if !addr.starts_with("*") {
-        return dwarfdata.get_addr_for_function(None, addr);
+    /// Continues while recording, by single-stepping until the inferior reaches a patched address
+    /// or stops for any other reason than the step.
+    fn record_until_stop(&mut self, mut signal: Option<Signal>) -> Result<Status, nix::Error> {
+        loop {
+            let rip = self.inferior.as_ref().unwrap().rip()?;
+            if self.breakpoints.is_inserted(rip) {
+                return Ok(Status::Stopped(Signal::SIGTRAP, rip));
+            }
+            match self.step_inferior(signal.take())? {
+                Status::Stopped(Signal::SIGTRAP, _) => (),
+                status => return Ok(status),
+            }
+        }
     }
 
-    let addr = &addr[1..];
-    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
-        &addr[2..]
-    } else {
-        &addr
-    };
+    /// Undoes the most recently recorded instruction and returns its log entry, or None if there
+    /// is nothing left to undo.
+    fn undo_instruction(&mut self) -> Result<Option<Entry>, nix::Error> {
+        let entry = match self.recording.as_mut().unwrap().pop() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let inferior = self.inferior.as_mut().unwrap();
+        for (addr, bytes) in entry.memory.iter().rev() {
+            inferior.write_bytes(*addr, bytes)?;
+        }
+        inferior.set_registers(entry.regs)?;
+        Ok(Some(entry))
+    }
+
+    /// Implements `reverse-stepi`: undoes one instruction.
+    fn reverse_stepi(&mut self) -> Result<Option<Status>, nix::Error> {
+        Ok(self
+            .undo_instruction()?
+            .map(|entry| Status::Stopped(Signal::SIGTRAP, entry.regs.rip as usize)))
+    }
 
-    usize::from_str_radix(addr_without_0x, 16).ok()
+    /// Implements `reverse-next`: goes back to the start of the previous line of the current
+    /// function, undoing any calls made from it along the way. Going back past the start of the
+    /// function stops at the call to it.
+    fn reverse_next(&mut self) -> Result<Option<Status>, nix::Error> {
+        let line_at = |debugger: &Debugger, addr: usize| {
+            debugger
+                .debug_data
+                .get_line_from_addr(addr)
+                .map(|line| (line.file, line.number))
+        };
+        let start_line = line_at(self, self.inferior.as_ref().unwrap().rip()?);
+        // How many calls deep we are relative to the starting frame
+        let mut depth = 0;
+        let depth_change = |flow: Flow| match flow {
+            // Undoing a call takes us back to the caller, undoing a return back into the callee
+            Flow::Call => -1,
+            Flow::Return => 1,
+            Flow::Syscall | Flow::Other => 0,
+        };
+
+        // Back up to the last instruction of the previous line
+        let line = loop {
+            let entry = match self.undo_instruction()? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+            depth += depth_change(entry.flow);
+            let rip = entry.regs.rip as usize;
+            if depth < 0 {
+                return Ok(Some(Status::Stopped(Signal::SIGTRAP, rip)));
+            }
+            // The prologue doesn't count as a line of its own, so from the first line of a
+            // function we go back to the call
+            let in_prologue = self
+                .debug_data
+                .get_function_info_from_addr(rip)
+                .map_or(false, |func| {
+                    rip < self.debug_data.skip_prologue(func.address)
+                });
+            let line = line_at(self, rip);
+            if depth == 0 && line != start_line && !in_prologue {
+                break line;
+            }
+        };
+        // Then to its first instruction
+        loop {
+            let (rip, flow) = match self.recording.as_ref().unwrap().last() {
+                Some(entry) => (entry.regs.rip as usize, entry.flow),
+                None => return Ok(None),
+            };
+            let new_depth = depth + depth_change(flow);
+            if new_depth < 0 || (new_depth == 0 && line_at(self, rip) != line) {
+                let rip = self.inferior.as_ref().unwrap().rip()?;
+                return Ok(Some(Status::Stopped(Signal::SIGTRAP, rip)));
+            }
+            self.undo_instruction()?;
+            depth = new_depth;
+        }
+    }
+
+    /// Implements `reverse-continue`: undoes instructions until reaching a breakpoint, or undoing
+    /// a write that changes the value of a watchpoint.
+    fn reverse_continue(&mut self) -> Result<Option<Status>, nix::Error> {
+        loop {
+            let entry = match self.undo_instruction()? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+            let rip = entry.regs.rip as usize;
+            let slots: Vec<usize> = self
+                .breakpoints
+                .watchpoints()
+                .filter(|wp| {
+                    wp.enabled
+                        && entry.memory.iter().any(|(addr, bytes)| {
+                            *addr < wp.addr + wp.len && wp.addr < addr + bytes.len()
+                        })
+                })
+                .map(|wp| wp.slot)
+                .collect();
+            for slot in slots {
+                if self.watchpoint_changed(slot) {
+                    return Ok(Some(Status::Watchpoint(slot, rip)));
+                }
+            }
+            if self.breakpoints.enabled_at(rip).next().is_some() && self.breakpoint_hit(rip) {
+                return Ok(Some(Status::Stopped(Signal::SIGTRAP, rip)));
+            }
+        }
+    }
+
+    /// Called when the inferior stops with a signal other than SIGTRAP. Applies the `handle`
+    /// policy for it: mentions the signal if it is neither printed as a stop nor silent, queues it
+    /// for delivery if it is passed to the program, and returns whether the inferior should stop.
+    fn signal_stops(&mut self, signal: Signal) -> bool {
+        let policy = self.signals.get(signal);
+        if policy.print && !policy.stop {
+            println!("Child received signal {}", signal);
+        }
+        if policy.pass {
+            self.pending_signal = Some(signal);
+        }
+        if policy.stop {
+            self.stop_signal = Some(signal);
+        }
+        policy.stop
+    }
+
+    /// Acts on a fork or exec of the inferior, after which it can be resumed.
+    fn handle_event(&mut self, event: Event) -> Result<(), nix::Error> {
+        match event {
+            Event::Fork(child, vfork) => self.follow_fork(child, vfork),
+            // The breakpoints were taken out of the memory the inferior shared with its child
+            Event::VforkDone => {
+                self.sync_breakpoints();
+                Ok(())
+            }
+            Event::Exec => {
+                self.exec_happened();
+                Ok(())
+            }
+        }
+    }
+
+    /// Lets go of one of the two processes after the inferior forked, as `follow-fork-mode` says.
+    /// A forked child starts out with a copy of the parent's breakpoints, which are taken out of
+    /// whichever process is let go. After a vfork the two share memory until the child calls
+    /// exec, so breakpoints are kept out of it until then.
+    fn follow_fork(&mut self, child: Pid, vfork: bool) -> Result<(), nix::Error> {
+        let mut child = Inferior::forked(child);
+        let call = if vfork { "vfork" } else { "fork" };
+        match self.follow_fork_mode {
+            FollowForkMode::Parent => {
+                println!(
+                    "[Detaching after {} from child process {}]",
+                    call,
+                    child.pid()
+                );
+                if vfork {
+                    self.breakpoints.remove_all(&mut child)?;
+                } else {
+                    self.breakpoints.restore_in(&mut child)?;
+                }
+                child.detach()
+            }
+            FollowForkMode::Child => {
+                let mut parent = self.inferior.take().unwrap();
+                println!(
+                    "[Attaching after process {} {} to child process {}]",
+                    parent.pid(),
+                    call,
+                    child.pid()
+                );
+                if vfork {
+                    self.breakpoints.remove_all(&mut parent)?;
+                } else {
+                    self.breakpoints.restore_in(&mut parent)?;
+                    for slot in 0..4 {
+                        parent.clear_watchpoint(slot)?;
+                    }
+                }
+                parent.detach()?;
+                self.inferior = Some(child);
+                self.recording = None;
+                self.forget_frames();
+                if !vfork {
+                    self.sync_breakpoints();
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Called when the inferior calls exec. The new program's memory has none of our breakpoints
+    /// in it; if it is a different program, its symbols are loaded and the breakpoints are looked
+    /// up again in them. Any `finish` or `next` in progress was waiting for a frame that is gone
+    /// now, so its traps are dropped.
+    fn exec_happened(&mut self) {
+        let pid = self.inferior.as_ref().unwrap().pid();
+        self.breakpoints.clear_inserted();
+        self.breakpoints.clear_internal();
+        self.forget_frames();
+        // The old program's history can't be undone in the new one
+        self.recording = None;
+        let exe = match std::fs::read_link(format!("/proc/{}/exe", pid)) {
+            Ok(exe) => exe,
+            Err(err) => {
+                println!(
+                    "Error: can't find the new executable of process {}: {}",
+                    pid, err
+                );
+                return;
+            }
+        };
+        println!(
+            "process {} is executing new program: {}",
+            pid,
+            exe.display()
+        );
+        if std::fs::canonicalize(&self.target).ok().as_ref() != Some(&exe) {
+            let path = exe.display().to_string();
+            match DwarfData::from_file(&path) {
+                Ok(debug_data) => {
+                    self.debug_data = debug_data;
+                    self.target = path;
+                    self.relocate_breakpoints();
+                }
+                Err(_) => println!("Warning: could not read debugging symbols from {}", path),
+            }
+        }
+        self.refresh_watchpoints();
+        self.sync_breakpoints();
+    }
+
+    /// Resolves every breakpoint's location again after a new program was loaded. Breakpoints
+    /// whose location no longer exists are disabled, and watchpoints are deleted.
+    fn relocate_breakpoints(&mut self) {
+        for bp in self.breakpoints.iter_mut() {
+            match parse_address(&bp.location, None, &self.debug_data) {
+                Ok(addr) => bp.addr = addr,
+                Err(err) => {
+                    println!("Breakpoint {} disabled: {}", bp.id, err);
+                    bp.enabled = false;
+                }
+            }
+        }
+        let watchpoints: Vec<usize> = self.breakpoints.watchpoints().map(|wp| wp.id).collect();
+        for id in watchpoints {
+            println!("Watchpoint {} deleted because the program has changed.", id);
+            self.breakpoints.remove_watchpoint(id);
+        }
+    }
+
+    /// Called when the watchpoint in debug register `slot` fires. Writes that store the value the
+    /// location already had don't count; otherwise the watchpoint's old and new values are
+    /// updated and the hit is counted.
+    fn watchpoint_changed(&mut self, slot: usize) -> bool {
+        let inferior = self.inferior.as_ref().unwrap();
+        let wp = match self.breakpoints.watchpoint_in_slot(slot) {
+            Some(wp) => wp,
+            None => return true,
+        };
+        let value = match inferior.read_bytes(wp.addr, wp.len) {
+            Ok(value) => value,
+            Err(_) => return true,
+        };
+        if value == wp.value {
+            return false;
+        }
+        wp.old_value = std::mem::replace(&mut wp.value, value);
+        wp.hit_count += 1;
+        true
+    }
+
+    /// Called when the inferior stops at system call `number`. Counts the hit for every enabled
+    /// catchpoint for it, and returns whether there were any.
+    fn syscall_caught(&mut self, number: u64) -> bool {
+        let mut caught = false;
+        for cp in self.breakpoints.catching(number) {
+            cp.hit_count += 1;
+            caught = true;
+        }
+        caught
+    }
+
+    /// Re-reads the current value of every watchpoint, e.g. after the inferior was restarted.
+    fn refresh_watchpoints(&mut self) {
+        let inferior = self.inferior.as_ref().unwrap();
+        for wp in self.breakpoints.watchpoints_mut() {
+            if let Ok(value) = inferior.read_bytes(wp.addr, wp.len) {
+                wp.old_value = value.clone();
+                wp.value = value;
+            }
+        }
+    }
+
+    /// Called when the inferior traps at the patched address `addr`. Counts the hit for every
+    /// enabled breakpoint there whose condition holds, and returns whether any of them should
+    /// actually stop the inferior. Addresses patched only for internal use (e.g. by `finish`)
+    /// always stop.
+    fn breakpoint_hit(&mut self, addr: usize) -> bool {
+        let candidates: Vec<(usize, Option<String>)> = self
+            .breakpoints
+            .enabled_at(addr)
+            .map(|bp| (bp.id, bp.condition.clone()))
+            .collect();
+        if candidates.is_empty() {
+            return true;
+        }
+
+        let mut stop = false;
+        for (id, condition) in candidates {
+            if let Some(condition) = condition {
+                match self
+                    .evaluate_expression(&condition)
+                    .and_then(|value| value.is_true())
+                {
+                    Ok(true) => (),
+                    Ok(false) => continue,
+                    Err(err) => {
+                        println!("Error in testing condition for breakpoint {}: {}", id, err);
+                        stop = true;
+                        continue;
+                    }
+                }
+            }
+            let bp = self.breakpoints.get_mut(id).unwrap();
+            bp.hit_count += 1;
+            if bp.ignore_count > 0 {
+                bp.ignore_count -= 1;
+            } else {
+                stop = true;
+                if bp.temporary {
+                    println!("Temporary breakpoint {} deleted", id);
+                    self.breakpoints.remove(id);
+                    self.sync_breakpoints();
+                }
+            }
+        }
+        stop
+    }
+
+    /// Executes a single instruction, stepping over the breakpoint at rip (if any) and delivering
+    /// the pending signal. A watchpoint that fired without changing its value is reported as an
+    /// ordinary step, and a `nostop` signal that arrives instead is passed on and the step retried.
+    fn step_instruction(&mut self) -> Result<Status, nix::Error> {
+        self.forget_frames();
+        self.stop_signal = None;
+        loop {
+            let signal = self.pending_signal.take();
+            let status = match self.step_over_breakpoint(signal)? {
+                Some(status) => status,
+                None => self.step_inferior(signal)?,
+            };
+            match status {
+                Status::Watchpoint(slot, rip) if !self.watchpoint_changed(slot) => {
+                    return Ok(Status::Stopped(Signal::SIGTRAP, rip))
+                }
+                Status::Stopped(signal, _) if signal != Signal::SIGTRAP => {
+                    if !self.signal_stops(signal) {
+                        continue;
+                    }
+                    return Ok(status);
+                }
+                // The step isn't finished until the fork or exec returns
+                Status::Event(event, _) => self.handle_event(event)?,
+                status => return Ok(status),
+            }
+        }
+    }
+
+    /// If the instruction that was just executed at `rip` (with stack pointer `rsp`) was a call,
+    /// returns the address it will return to. A call pushes the address of the next instruction,
+    /// which is at most 15 bytes (the longest x86-64 instruction) past `rip`.
+    fn called_from(&self, rip: usize, rsp: usize) -> Result<Option<usize>, nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        let new_rsp = inferior.rsp()?;
+        if new_rsp + 8 != rsp {
+            return Ok(None);
+        }
+        let return_addr = inferior.read_word(new_rsp)?;
+        if return_addr > rip && return_addr <= rip + 15 {
+            Ok(Some(return_addr))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Continues until the inferior reaches `addr` with a stack pointer of at least `sp`, so that
+    /// recursive invocations of the same code are skipped. An internal breakpoint is inserted at
+    /// `addr` for the duration. Returns early if anything else stops the inferior.
+    fn run_to(&mut self, addr: usize, sp: usize) -> Result<Status, nix::Error> {
+        self.run_to_any(&[(addr, sp)])
+    }
+
+    /// Like `run_to`, but stops at whichever of several `(addr, sp)` targets is reached first.
+    fn run_to_any(&mut self, targets: &[(usize, usize)]) -> Result<Status, nix::Error> {
+        let internal: Vec<usize> = targets.iter().map(|(addr, _)| *addr).collect();
+        for addr in &internal {
+            self.breakpoints
+                .insert_internal(self.inferior.as_mut().unwrap(), *addr)?;
+        }
+
+        let status = self.continue_to_any(targets);
+        match status {
+            Ok(Status::Exited(_)) | Ok(Status::Signaled(_)) => {
+                self.breakpoints.clear_inserted();
+                self.breakpoints.clear_internal();
+            }
+            Ok(_) => {
+                for addr in internal {
+                    self.breakpoints
+                        .remove_internal(self.inferior.as_mut().unwrap(), addr)?;
+                }
+            }
+            // Don't leave our traps behind, but report the error that got us here
+            Err(_) => {
+                for addr in internal {
+                    if let Some(inferior) = self.inferior.as_mut() {
+                        self.breakpoints.remove_internal(inferior, addr).ok();
+                    }
+                }
+                self.breakpoints.clear_internal();
+            }
+        }
+        status
+    }
+
+    /// Continues the inferior until it stops anywhere but at one of the `run_to_any` traps with
+    /// too low a stack pointer.
+    fn continue_to_any(&mut self, targets: &[(usize, usize)]) -> Result<Status, nix::Error> {
+        loop {
+            let status = self.continue_inferior()?;
+            match status {
+                // Only our own trap is skipped; a user breakpoint at the same place always stops
+                Status::Stopped(Signal::SIGTRAP, rip)
+                    if self.breakpoints.is_internal(rip)
+                        && self.breakpoints.enabled_at(rip).next().is_none() =>
+                {
+                    let rsp = self.inferior.as_ref().unwrap().rsp()?;
+                    if targets.iter().any(|(addr, sp)| *addr == rip && rsp >= *sp) {
+                        return Ok(status);
+                    }
+                }
+                _ => return Ok(status),
+            }
+        }
+    }
+
+    /// Implements `until` and `advance`. With a location, runs until it is reached or the current
+    /// frame returns; `until` only counts the location when it's reached in the current frame or
+    /// one of its callers, while `advance` takes it in any frame. A plain `until` is like `next`,
+    /// except that it doesn't stop when jumping back to an earlier line, so it gets out of loops.
+    /// Returns None if the location couldn't be resolved.
+    fn until(
+        &mut self,
+        location: Option<&str>,
+        advance: bool,
+    ) -> Result<Option<Status>, nix::Error> {
+        let (return_addr, caller_sp) = self.frame_return_address()?;
+        let location = match location {
+            Some(location) => location,
+            None => {
+                let start = self.inferior.as_ref().unwrap().rip()?;
+                loop {
+                    let status = self.step_line(false)?;
+                    let in_frame = self.inferior.as_ref().unwrap().rsp()? < caller_sp;
+                    match status {
+                        Status::Stopped(Signal::SIGTRAP, rip) if rip <= start && in_frame => (),
+                        _ => return Ok(Some(status)),
+                    }
+                }
+            }
+        };
+
+        let default_file = self.default_file();
+        let addr = match parse_address(location, default_file.as_deref(), &self.debug_data) {
+            Ok(addr) => addr,
+            Err(err) => {
+                println!("{}", err);
+                return Ok(None);
+            }
+        };
+        let sp = if advance {
+            0
+        } else {
+            self.inferior.as_ref().unwrap().rsp()?
+        };
+        self.run_to_any(&[(addr, sp), (return_addr, caller_sp)])
+            .map(Some)
+    }
+
+    /// Steps until the inferior reaches the beginning of a different source line. Calls are
+    /// stepped into if `into` is set and the callee has line information; otherwise they run to
+    /// completion. Returning into code without line information (e.g. out of main) just continues.
+    fn step_line(&mut self, into: bool) -> Result<Status, nix::Error> {
+        let start_line = self
+            .debug_data
+            .get_line_from_addr(self.inferior.as_ref().unwrap().rip()?);
+        loop {
+            let inferior = self.inferior.as_ref().unwrap();
+            let (rip, rsp) = (inferior.rip()?, inferior.rsp()?);
+            let status = self.step_instruction()?;
+            let mut new_rip = match status {
+                Status::Stopped(Signal::SIGTRAP, new_rip) => new_rip,
+                _ => return Ok(status),
+            };
+
+            if let Some(return_addr) = self.called_from(rip, rsp)? {
+                if !into || self.debug_data.get_line_from_addr(new_rip).is_none() {
+                    match self.run_to(return_addr, rsp)? {
+                        Status::Stopped(Signal::SIGTRAP, addr) if addr == return_addr => {
+                            new_rip = addr
+                        }
+                        status => return Ok(status),
+                    }
+                } else {
+                    // Stepped into a function we have line info for: stop after its prologue, like
+                    // a breakpoint on the function would. The prologue makes no calls, so the
+                    // first time we get there is in this frame.
+                    let body = self.debug_data.skip_prologue(new_rip);
+                    if body != new_rip {
+                        match self.run_to(body, 0)? {
+                            Status::Stopped(Signal::SIGTRAP, addr) if addr == body => {
+                                return Ok(Status::Stopped(Signal::SIGTRAP, addr))
+                            }
+                            status => return Ok(status),
+                        }
+                    }
+                }
+            }
+
+            match self.debug_data.get_line_from_addr(new_rip) {
+                Some(line) => {
+                    let same_line = start_line.as_ref().map_or(false, |start| {
+                        start.file == line.file && start.number == line.number
+                    });
+                    // Jumping backwards to the start of the same line (a loop) counts as a new line
+                    if self.debug_data.is_line_start(new_rip) && (!same_line || new_rip < rip) {
+                        return Ok(Status::Stopped(Signal::SIGTRAP, new_rip));
+                    }
+                }
+                None => return self.continue_inferior(),
+            }
+        }
+    }
+
+    /// Returns the return address of the current frame, along with the stack pointer the caller
+    /// will have once the frame returns. These come from unwinding the stack; without CFI for the
+    /// current function we fall back to the frame pointer. At the very first instruction of a
+    /// function `push rbp` hasn't run yet, so the return address is at the top of the stack
+    /// instead of above rbp.
+    fn frame_return_address(&self) -> Result<(usize, usize), nix::Error> {
+        let inferior = self.inferior.as_ref().unwrap();
+        if let Some(caller) = inferior.unwind_stack(&self.debug_data)?.get(1) {
+            return Ok((caller.rip, caller.rsp));
+        }
+        let rip = inferior.rip()?;
+        let at_entry = self
+            .debug_data
+            .get_function_info_from_addr(rip)
+            .map_or(false, |func| func.address == rip);
+        let caller_sp = if at_entry {
+            inferior.rsp()? + 8
+        } else {
+            inferior.rbp()? + 16
+        };
+        Ok((inferior.read_word(caller_sp - 8)?, caller_sp))
+    }
+
+    /// Runs until the current function returns to its caller.
+    fn finish(&mut self) -> Result<Status, nix::Error> {
+        let (return_addr, caller_sp) = self.frame_return_address()?;
+        self.run_to(return_addr, caller_sp)
+    }
+
+    /// Unwinds the inferior's stack into `frames`, unless that was already done since it last
+    /// ran. Returns false (after printing why) if unwinding failed.
+    fn unwind_frames(&mut self) -> bool {
+        if !self.frames.is_empty() {
+            return true;
+        }
+        match self
+            .inferior
+            .as_ref()
+            .unwrap()
+            .unwind_stack(&self.debug_data)
+        {
+            Ok(frames) => {
+                self.frames = frames;
+                self.selected_frame = 0;
+                true
+            }
+            Err(err) => {
+                println!("Error unwinding the stack: {}", err);
+                false
+            }
+        }
+    }
+
+    /// Forgets the unwound stack and selects the innermost frame again, since the inferior is
+    /// about to run.
+    fn forget_frames(&mut self) {
+        self.frames.clear();
+        self.selected_frame = 0;
+    }
+
+    fn select_frame(&mut self, index: usize) {
+        self.selected_frame = index;
+        self.list_position = None;
+        print_frame(index, &self.frames[index], &self.debug_data);
+    }
+
+    /// Returns the registers of the selected frame.
+    fn selected_registers(&self) -> Result<Registers, nix::Error> {
+        match self.frames.get(self.selected_frame) {
+            Some(frame) => Ok(*frame),
+            None => Ok(Registers::from_user_regs(
+                &self.inferior.as_ref().unwrap().registers()?,
+            )),
+        }
+    }
+
+    /// Returns an address inside the code the selected frame is running. For outer frames, rip is
+    /// a return address, which may lie just past the end of the calling function.
+    fn selected_pc(&self) -> Result<usize, nix::Error> {
+        let rip = self.selected_registers()?.rip;
+        if self.selected_frame > 0 {
+            Ok(rip.wrapping_sub(1))
+        } else {
+            Ok(rip)
+        }
+    }
+
+    /// Returns the selected frame, for evaluating locations in.
+    fn selected_stack_frame(&self) -> Result<StackFrame<'_>, nix::Error> {
+        Ok(StackFrame {
+            debugger: self,
+            registers: self.selected_registers()?,
+            is_innermost: self.selected_frame == 0,
+        })
+    }
+
+    /// Prints the parameters (if `args` is true) or the locals of the selected frame's function.
+    fn print_frame_variables(&self, args: bool) {
+        let pc = match self.selected_pc() {
+            Ok(pc) => pc,
+            Err(err) => {
+                println!("Error reading registers: {}", err);
+                return;
+            }
+        };
+        let func = match self.debug_data.get_function_info_from_addr(pc) {
+            Some(func) => func,
+            None => {
+                println!("No symbol table info available.");
+                return;
+            }
+        };
+        let vars: Vec<&Variable> = func
+            .variables
+            .iter()
+            .filter(|var| var.is_parameter == args)
+            .collect();
+        if vars.is_empty() {
+            println!("{}", if args { "No arguments." } else { "No locals." });
+        }
+        for var in vars {
+            self.print_expression(&var.name);
+        }
+    }
+
+    /// Parses `text` as a C expression. The program's typedef names are recognized in casts.
+    fn parse_expression(&self, text: &str) -> Result<Expression, String> {
+        expression::parse(text, &|name| self.debug_data.find_type(name).is_some())
+    }
+
+    /// Evaluates the C expression `text` in the selected frame.
+    fn evaluate_expression(&self, text: &str) -> Result<Value, String> {
+        evaluate(&self.parse_expression(text)?, self)
+    }
+
+    /// Formats a value of `value_type` for printing. A pointer to char also shows the string it
+    /// points to, and a pointer to a function the function's name.
+    fn format_value(&self, value_type: &Type, bytes: &[u8]) -> String {
+        let value = value_type.format_value(bytes);
+        let target = self.debug_data.pointer_target(value_type);
+        let addr = value_type.value_as_i64(bytes) as usize;
+        match target.map(|target| &target.resolved().kind) {
+            Some(TypeKind::Base(Encoding::SignedChar))
+            | Some(TypeKind::Base(Encoding::UnsignedChar)) => {
+                match self.inferior.as_ref().unwrap().read_string(addr, 200) {
+                    Ok(string) => format!("{} {:?}", value, string),
+                    Err(_) => format!(
+                        "{} <error: Cannot access memory at address {}>",
+                        value, value
+                    ),
+                }
+            }
+            Some(TypeKind::Function) => match self.symbolize(addr) {
+                Some(symbol) => format!("{} {}", value, symbol),
+                None => value,
+            },
+            _ => value,
+        }
+    }
+
+    /// Evaluates the C expression `text` and formats its value for printing. A function is shown
+    /// as its type and address.
+    fn format_expression(&self, text: &str) -> Result<String, String> {
+        let value = match self.evaluate_expression(text) {
+            // Like gdb, show a variable that is gone as such, but refuse to compute with it
+            Err(ref err) if err == OPTIMIZED_OUT => {
+                return match self.parse_expression(text)? {
+                    Expression::Variable(_) => Ok("<optimized out>".to_string()),
+                    _ => Err(err.clone()),
+                }
+            }
+            value => value?,
+        };
+        Ok(match (&value.value_type.resolved().kind, value.address) {
+            (TypeKind::Function, Some(addr)) => format!(
+                "{{{}}} {:#x} {}",
+                value.value_type.name,
+                addr,
+                self.symbolize(addr).unwrap_or_default()
+            ),
+            _ => self.format_value(&value.value_type, &value.bytes),
+        })
+    }
+
+    /// Prints the value of the C expression `text`.
+    fn print_expression(&self, text: &str) {
+        match self.format_expression(text) {
+            Ok(value) => println!("{} = {}", text, value),
+            Err(err) => println!("{}", err),
+        }
+    }
+
+    /// Registers `expression` to be printed every time the inferior stops, and prints it now if
+    /// there is an inferior.
+    fn add_display(&mut self, expression: &str) {
+        if let Err(err) = self.parse_expression(expression) {
+            println!("{}", err);
+            return;
+        }
+        let id = self.next_display_id;
+        self.next_display_id += 1;
+        self.displays.push(AutoDisplay {
+            id,
+            expression: expression.to_string(),
+        });
+        if self.inferior.is_some() {
+            self.print_display(&self.displays[self.displays.len() - 1]);
+        }
+    }
+
+    fn print_display(&self, display: &AutoDisplay) {
+        match self.format_expression(&display.expression) {
+            Ok(value) => println!("{}: {} = {}", display.id, display.expression, value),
+            Err(err) => println!("{}: {} = <error: {}>", display.id, display.expression, err),
+        }
+    }
+
+    /// Prints every `display` expression, as is done whenever the inferior stops.
+    fn print_displays(&self) {
+        if self.inferior.is_none() {
+            return;
+        }
+        for display in &self.displays {
+            self.print_display(display);
+        }
+    }
+
+    /// Lists the `display` expressions, for `info display`.
+    fn print_display_list(&self) {
+        if self.displays.is_empty() {
+            println!("There are no auto-display expressions now.");
+            return;
+        }
+        println!("Auto-display expressions now in effect:");
+        println!("Num Expression");
+        for display in &self.displays {
+            println!("{:<3} {}", format!("{}:", display.id), display.expression);
+        }
+    }
+
+    /// Carries out `assignment` (e.g. `x = 10` or `*(int *)0x404028 = 5`), writing the new value
+    /// to the inferior's memory.
+    fn set_variable(&mut self, assignment: &str) {
+        let value = self
+            .parse_expression(assignment)
+            .and_then(|expression| match expression {
+                Expression::Assign(lhs, rhs) => expression::assignment(&lhs, &rhs, self),
+                _ => Err(format!(
+                    "Expected an assignment, as in `set var {} = 1`.",
+                    assignment
+                )),
+            });
+        let value = match value {
+            Ok(value) => value,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        let addr = value.address.unwrap();
+        if let Err(err) = self
+            .inferior
+            .as_mut()
+            .unwrap()
+            .write_bytes(addr, &value.bytes)
+        {
+            println!("Cannot access memory at address {:#x}: {}", addr, err);
+            return;
+        }
+        // The write isn't a change the inferior made, so watchpoints shouldn't report it
+        self.refresh_watchpoints();
+    }
+
+    /// Sets a breakpoint at `location` that only stops if `condition` holds. A temporary breakpoint
+    /// deletes itself the first time it stops the inferior.
+    fn add_breakpoint(&mut self, location: &str, condition: Option<String>, temporary: bool) {
+        let default_file = self.default_file();
+        let location = match qualify_location(location, default_file.as_deref(), &self.debug_data) {
+            Ok(location) => location,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        let addr = match parse_address(&location, None, &self.debug_data) {
+            Ok(addr) => addr,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        if let Some(Err(err)) = condition.as_ref().map(|text| self.parse_expression(text)) {
+            println!("{}", err);
+            return;
+        }
+        if self.breakpoints.iter().any(|bp| bp.addr == addr) {
+            println!("Note: another breakpoint is already set at {:#x}", addr);
+        }
+        let id = self.breakpoints.add(addr, &location);
+        let bp = self.breakpoints.get_mut(id).unwrap();
+        bp.condition = condition;
+        bp.temporary = temporary;
+        if let Some(inferior) = self
+            .inferior
+            .as_mut()
+            .filter(|inferior| !inferior.is_core())
+        {
+            if let Err(err) = self.breakpoints.insert_at(inferior, addr) {
+                println!("Cannot insert breakpoint at {:#x}: {}", addr, err);
+                self.breakpoints.remove(id);
+                return;
+            }
+        }
+        if temporary {
+            println!("Temporary breakpoint {} at {:#x}", id, addr);
+        } else {
+            println!("Set breakpoint {} at {:#x}", id, addr);
+        }
+    }
+
+    /// Prints the inferior's threads and where each of them is, marking the current one.
+    fn print_threads(&self) {
+        let inferior = self.inferior.as_ref().unwrap();
+        let current = inferior.current_thread().tid;
+        println!("  {:<4} {:<12} Frame", "Id", "Target Id");
+        for thread in inferior.threads() {
+            let frame = match inferior.thread_registers(thread.tid) {
+                Ok(regs) => {
+                    let func = self
+                        .debug_data
+                        .get_function_from_addr(regs.rip)
+                        .unwrap_or_else(|| "??".to_string());
+                    match self.debug_data.get_line_from_addr(regs.rip) {
+                        Some(line) => format!("{} {}", func, line),
+                        None => format!("{} {:#x}", func, regs.rip),
+                    }
+                }
+                Err(err) => format!("(error reading registers: {})", err),
+            };
+            println!(
+                "{} {:<4} {:<12} {}",
+                if thread.tid == current { "*" } else { " " },
+                thread.id,
+                format!("LWP {}", thread.tid),
+                frame
+            );
+        }
+    }
+
+    /// Prints the registers called `names`, or all of them if `names` is empty. rip, rsp and rbp
+    /// are those of the selected frame.
+    fn print_registers(&self, names: &[String]) {
+        let all: Vec<String> = REGISTER_NAMES.iter().map(|name| name.to_string()).collect();
+        let names = if names.is_empty() { &all[..] } else { names };
+        let frame = match self.selected_registers() {
+            Ok(frame) => frame,
+            Err(err) => {
+                println!("Error reading registers: {}", err);
+                return;
+            }
+        };
+        for name in names {
+            let value = match name.as_str() {
+                "rip" | "pc" => Some(frame.rip as u64),
+                "rsp" | "sp" => Some(frame.rsp as u64),
+                "rbp" | "fp" => Some(frame.rbp as u64),
+                _ => match self.inferior.as_ref().unwrap().register(name) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        println!("Error reading registers: {}", err);
+                        return;
+                    }
+                },
+            };
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    println!("Invalid register `{}'", name);
+                    continue;
+                }
+            };
+            let natural = match name.as_str() {
+                "rip" | "pc" => match self.debug_data.get_function_from_addr(value as usize) {
+                    Some(func) => format!("{:#x} <{}>", value, func),
+                    None => format!("{:#x}", value),
+                },
+                "rsp" | "sp" | "rbp" | "fp" | "eflags" => format!("{:#x}", value),
+                _ => format!("{}", value as i64),
+            };
+            println!("{:<15}{:<#19x}{}", name, value, natural);
+        }
+    }
+
+    /// Works out the address `x` should examine from the expression `address`: the value of a
+    /// number, pointer or `$reg`, or where an array or function is.
+    fn examine_address(&self, address: &str) -> Result<usize, String> {
+        self.evaluate_expression(address)?.as_address()
+    }
+
+    /// Prints `count` units of memory starting at `addr`, as `x/Nfu` does. `s` prints `count`
+    /// NUL-terminated strings instead.
+    fn examine(&self, count: usize, format: char, size: usize, addr: usize) {
+        let inferior = self.inferior.as_ref().unwrap();
+        if format == 'i' {
+            match self.read_code(addr, count * MAX_INSTRUCTION_LEN) {
+                Ok(code) => {
+                    for instruction in disassemble(&code, addr, count) {
+                        let symbol = self
+                            .symbolize(instruction.addr)
+                            .map_or(String::new(), |symbol| format!(" {}", symbol));
+                        println!(
+                            "{}{:#x}{}:\t{}",
+                            self.pc_marker(instruction.addr),
+                            instruction.addr,
+                            symbol,
+                            self.instruction_text(&instruction)
+                        );
+                    }
+                }
+                Err(err) => println!("Cannot access memory at address {:#x}: {}", addr, err),
+            }
+            return;
+        }
+        if format == 's' {
+            let mut addr = addr;
+            for _ in 0..count {
+                match inferior.read_string(addr, 200) {
+                    Ok(string) => {
+                        println!("{:#x}:\t{:?}", addr, string);
+                        addr += string.len() + 1;
+                    }
+                    Err(err) => {
+                        println!("Cannot access memory at address {:#x}: {}", addr, err);
+                        return;
+                    }
+                }
+            }
+            return;
+        }
+
+        let bytes = match inferior.read_bytes(addr, count * size) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                println!("Cannot access memory at address {:#x}: {}", addr, err);
+                return;
+            }
+        };
+        let per_line = match (format, size) {
+            ('c', _) | (_, 1) | (_, 2) => 8,
+            (_, 4) => 4,
+            _ => 2,
+        };
+        let value_type = match format {
+            'c' => Type::base("char", 1, Encoding::SignedChar),
+            _ => Type::base("long", size, Encoding::Signed),
+        };
+        for (line, chunk) in bytes.chunks(size * per_line).enumerate() {
+            let values: Vec<String> = chunk
+                .chunks(size)
+                .map(|unit| match format {
+                    'x' => {
+                        let hex: Vec<String> = unit
+                            .iter()
+                            .rev()
+                            .map(|byte| format!("{:02x}", byte))
+                            .collect();
+                        format!("0x{}", hex.concat())
+                    }
+                    _ => value_type.format_value(unit),
+                })
+                .collect();
+            println!(
+                "{:#x}:\t{}",
+                addr + line * size * per_line,
+                values.join("\t")
+            );
+        }
+    }
+
+    /// Prints the source line the inferior just stopped at, and makes the next `list` show the
+    /// code around it.
+    fn print_stop_line(&mut self, rip: usize) {
+        if let Some(line) = self.debug_data.get_line_from_addr(rip) {
+            self.print_source_lines(&line.file, line.number, line.number, Some(line.number));
+        }
+        self.list_position = None;
+    }
+
+    /// Returns the source location of the selected frame, if there is a process whose registers
+    /// can be read and we have line info for where it is.
+    fn selected_line(&self) -> Option<Line> {
+        self.inferior.as_ref()?;
+        self.debug_data.get_line_from_addr(self.selected_pc().ok()?)
+    }
+
+    /// Implements `list`: with no argument, continues the last listing or shows the code around
+    /// the selected frame (or `main` when there is no process); otherwise shows the code around a
+    /// line number in the current file, or around the start of a function.
+    fn list(&mut self, location: Option<&str>) {
+        let current = self.selected_line();
+        let (file, center) = match location {
+            None => {
+                if let Some((file, first)) = self.list_position.clone() {
+                    let current = current
+                        .filter(|line| line.file == file)
+                        .map(|line| line.number);
+                    self.print_source_lines(&file, first, first + LIST_SIZE - 1, current);
+                    return;
+                }
+                match current.clone().or_else(|| self.function_line("main")) {
+                    Some(line) => (line.file, line.number),
+                    None => {
+                        println!("No line number information available.");
+                        return;
+                    }
+                }
+            }
+            Some(location) => match location.parse::<usize>() {
+                Ok(number) => {
+                    let file = match self.list_position.clone() {
+                        Some((file, _)) => file,
+                        None => match current.clone().or_else(|| self.function_line("main")) {
+                            Some(line) => line.file,
+                            None => {
+                                println!("No line number information available.");
+                                return;
+                            }
+                        },
+                    };
+                    (file, number)
+                }
+                Err(_) => match self.function_line(location) {
+                    Some(line) => (line.file, line.number),
+                    None => {
+                        println!("Function \"{}\" not defined.", location);
+                        return;
+                    }
+                },
+            },
+        };
+
+        let first = center.saturating_sub(LIST_SIZE / 2).max(1);
+        let current = current
+            .filter(|line| line.file == file)
+            .map(|line| line.number);
+        self.print_source_lines(&file, first, first + LIST_SIZE - 1, current);
+    }
+
+    /// Returns the file a line number without a file name refers to: the selected frame's, or
+    /// main's when there is no process.
+    fn default_file(&self) -> Option<String> {
+        self.selected_line()
+            .or_else(|| self.function_line("main"))
+            .map(|line| line.file)
+    }
+
+    /// Returns the source location of the start of the function called `name`.
+    fn function_line(&self, name: &str) -> Option<Line> {
+        let addr = self.debug_data.get_addr_for_function(None, name)?;
+        self.debug_data.get_line_from_addr(addr)
+    }
+
+    /// Prints lines `first` through `last` of `file`, marking line `current`, and remembers where
+    /// a following `list` should continue.
+    fn print_source_lines(
+        &mut self,
+        file: &str,
+        first: usize,
+        last: usize,
+        current: Option<usize>,
+    ) {
+        let lines = match self.sources.lines(file) {
+            Some(lines) => lines,
+            None => {
+                println!("{}\t{}: No such file or directory.", first, file);
+                return;
+            }
+        };
+        if first > lines.len() {
+            println!(
+                "Line number {} out of range; \"{}\" has {} lines.",
+                first,
+                file,
+                lines.len()
+            );
+            return;
+        }
+        let last = last.min(lines.len());
+        for number in first..=last {
+            let marker = if Some(number) == current {
+                "=> "
+            } else {
+                "   "
+            };
+            println!("{}{:<4} {}", marker, number, lines[number - 1]);
+        }
+        self.list_position = Some((file.to_string(), last + 1));
+    }
+
+    /// Reads `len` bytes of code at `addr`, with the original instruction bytes put back where
+    /// breakpoints are inserted.
+    fn read_code(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut code = self.inferior.as_ref().unwrap().read_bytes(addr, len)?;
+        for (offset, byte) in code.iter_mut().enumerate() {
+            if let Some(orig_byte) = self.breakpoints.orig_byte(addr + offset) {
+                *byte = orig_byte;
+            }
+        }
+        Ok(code)
+    }
+
+    /// Describes `addr` as `<function+offset>`, if it is inside a function we know of.
+    fn symbolize(&self, addr: usize) -> Option<String> {
+        match self.debug_data.get_function_info_from_addr(addr) {
+            Some(func) if addr == func.address => Some(format!("<{}>", func.name)),
+            Some(func) => Some(format!("<{}+{}>", func.name, addr - func.address)),
+            None => self
+                .debug_data
+                .get_function_from_addr(addr)
+                .map(|name| format!("<{}>", name)),
+        }
+    }
+
+    /// Returns the instruction's text, with the target of a direct call or jump annotated with the
+    /// function it lands in.
+    fn instruction_text(&self, instruction: &DecodedInstruction) -> String {
+        match instruction
+            .branch_target
+            .and_then(|target| self.symbolize(target))
+        {
+            Some(symbol) => format!("{} {}", instruction.text, symbol),
+            None => instruction.text.clone(),
+        }
+    }
+
+    /// Returns the marker for the start of a disassembly line, which points out the selected
+    /// frame's pc (if its registers can be read).
+    fn pc_marker(&self, addr: usize) -> &'static str {
+        if self
+            .selected_registers()
+            .map_or(false, |frame| frame.rip == addr)
+        {
+            "=> "
+        } else {
+            "   "
+        }
+    }
+
+    /// Disassembles the function named or containing the address given by `location`, or the one
+    /// the selected frame is in. Without debug info for it, a few instructions from the address
+    /// are shown instead.
+    fn disassemble(&self, location: Option<&str>) {
+        let addr = match location {
+            None => match self.selected_pc() {
+                Ok(pc) => pc,
+                Err(err) => {
+                    println!("Error reading registers: {}", err);
+                    return;
+                }
+            },
+            Some(location) => match self.debug_data.get_addr_for_function(None, location) {
+                Some(addr) => addr,
+                // Anything else is an expression for an address, as with `x`
+                None => match self.examine_address(location) {
+                    Ok(addr) => addr,
+                    Err(err) => {
+                        println!("{}", err);
+                        return;
+                    }
+                },
+            },
+        };
+
+        let func = self.debug_data.get_function_info_from_addr(addr);
+        let (start, len, max_count) = match func {
+            Some(func) => (func.address, func.text_length, usize::max_value()),
+            None => (
+                addr,
+                DISASSEMBLE_COUNT * MAX_INSTRUCTION_LEN,
+                DISASSEMBLE_COUNT,
+            ),
+        };
+        let code = match self.read_code(start, len) {
+            Ok(code) => code,
+            Err(err) => {
+                println!("Cannot access memory at address {:#x}: {}", start, err);
+                return;
+            }
+        };
+        let instructions = disassemble(&code, start, max_count);
+
+        match func {
+            Some(func) => println!("Dump of assembler code for function {}:", func.name),
+            None => println!(
+                "Dump of assembler code from {:#x} to {:#x}:",
+                start,
+                instructions
+                    .last()
+                    .map_or(start, |last| last.addr + last.len)
+            ),
+        }
+        for instruction in &instructions {
+            let location = match func {
+                Some(func) => format!(" <+{}>", instruction.addr - func.address),
+                None => self
+                    .symbolize(instruction.addr)
+                    .map_or(String::new(), |symbol| format!(" {}", symbol)),
+            };
+            println!(
+                "{}{:#018x}{}:\t{}",
+                self.pc_marker(instruction.addr),
+                instruction.addr,
+                location,
+                self.instruction_text(instruction)
+            );
+        }
+        println!("End of assembler dump.");
+    }
+
+    /// Sets a hardware watchpoint on the object an expression refers to, or on `*addr`. Raw
+    /// addresses are watched for the largest size (up to a word) they are aligned to.
+    fn add_watchpoint(&mut self, expression: &str) {
+        let raw_address = expression.starts_with('*')
+            && expression[1..].starts_with(|c: char| c.is_ascii_digit());
+        let (addr, len, entity_type) = if raw_address {
+            let addr = match parse_address(expression, None, &self.debug_data) {
+                Ok(addr) => addr,
+                Err(err) => {
+                    println!("{}", err);
+                    return;
+                }
+            };
+            let len = [8, 4, 2, 1].iter().find(|len| addr % *len == 0).unwrap();
+            (addr, *len, None)
+        } else {
+            match self.evaluate_expression(expression) {
+                Ok(Value {
+                    value_type,
+                    address: Some(addr),
+                    ..
+                }) => (addr, value_type.size, Some(value_type)),
+                Ok(_) => {
+                    println!("Cannot watch constant value `{}'.", expression);
+                    return;
+                }
+                Err(err) => {
+                    println!("{}", err);
+                    return;
+                }
+            }
+        };
+        if ![1, 2, 4, 8].contains(&len) || addr % len != 0 {
+            println!(
+                "Cannot watch {}: hardware watchpoints need an aligned 1, 2, 4 or 8 byte location",
+                expression
+            );
+            return;
+        }
+
+        let value = match self.inferior.as_ref().unwrap().read_bytes(addr, len) {
+            Ok(value) => value,
+            Err(err) => {
+                println!("Cannot access memory at address {:#x}: {}", addr, err);
+                return;
+            }
+        };
+        match self
+            .breakpoints
+            .add_watchpoint(addr, len, expression, entity_type, value)
+        {
+            Some(id) => {
+                println!("Hardware watchpoint {}: {}", id, expression);
+                self.sync_breakpoints();
+            }
+            None => println!("All 4 hardware debug registers are in use."),
+        }
+    }
+
+    /// Sets a catchpoint for the system calls named (or numbered) in `syscalls`, or for every
+    /// system call if none are given.
+    fn add_catchpoint(&mut self, syscalls: &[String]) {
+        let mut numbers = Vec::new();
+        for syscall in syscalls {
+            match syscall
+                .parse::<u64>()
+                .ok()
+                .or_else(|| syscall_number(syscall))
+            {
+                Some(number) => numbers.push(number),
+                None => {
+                    println!("Unknown syscall name '{}'.", syscall);
+                    return;
+                }
+            }
+        }
+        let names: Vec<String> = numbers
+            .iter()
+            .map(|number| format!("'{}' [{}]", syscall_name(*number).unwrap_or("?"), number))
+            .collect();
+        let id = self.breakpoints.add_catchpoint(numbers);
+        match names.len() {
+            0 => println!("Catchpoint {} (any syscall)", id),
+            1 => println!("Catchpoint {} (syscall {})", id, names[0]),
+            _ => println!("Catchpoint {} (syscalls {})", id, names.join(" ")),
+        }
+        self.sync_breakpoints();
+    }
+
+    /// Describes a stop at system call `number`: its arguments on entry, or its return value
+    /// (`ret`) on exit.
+    fn print_syscall(&mut self, number: u64, ret: Option<i64>) {
+        let ids: Vec<String> = self
+            .breakpoints
+            .catching(number)
+            .map(|cp| cp.id.to_string())
+            .collect();
+        let name = match syscall_name(number) {
+            Some(name) => name.to_string(),
+            None => number.to_string(),
+        };
+        let what = match ret {
+            None => format!(
+                "call to syscall {}({})",
+                name,
+                self.syscall_arguments(number)
+            ),
+            Some(ret) => format!(
+                "returned from syscall {} = {}",
+                name,
+                format_syscall_return(ret)
+            ),
+        };
+        println!("\nCatchpoint {} ({})", ids.join(", "), what);
+    }
+
+    /// Formats the arguments of the system call the inferior is entering, from the registers they
+    /// are passed in. Arguments that are paths are shown as strings.
+    fn syscall_arguments(&self, number: u64) -> String {
+        let inferior = self.inferior.as_ref().unwrap();
+        let paths = syscall_name(number).map_or(&[][..], path_arguments);
+        ARGUMENT_REGISTERS[..argument_count(number)]
+            .iter()
+            .enumerate()
+            .map(|(index, register)| {
+                let value = inferior.register(register).ok().flatten().unwrap_or(0);
+                if paths.contains(&index) {
+                    if let Ok(path) = inferior.read_string(value as usize, 256) {
+                        return format!("{:?}", path);
+                    }
+                }
+                if (value as i64).abs() < 0x10000 {
+                    format!("{}", value as i64)
+                } else {
+                    format!("{:#x}", value)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Brings the 0xcc patches in the live inferior (if any) in line with the breakpoint table. A
+    /// core file is left alone.
+    fn sync_breakpoints(&mut self) {
+        if let Some(inferior) = self
+            .inferior
+            .as_mut()
+            .filter(|inferior| !inferior.is_core())
+        {
+            if let Err(err) = self.breakpoints.sync(inferior) {
+                println!("Warning: failed to update breakpoints: {}", err);
+            }
+        }
+    }
+
+    /// Enables or disables the given breakpoints (or all of them, if `ids` is empty).
+    fn set_breakpoints_enabled(&mut self, ids: &[usize], enabled: bool) {
+        if ids.is_empty() {
+            for bp in self.breakpoints.iter_mut() {
+                bp.enabled = enabled;
+            }
+            for wp in self.breakpoints.watchpoints_mut() {
+                wp.enabled = enabled;
+            }
+            for cp in self.breakpoints.catchpoints_mut() {
+                cp.enabled = enabled;
+            }
+        }
+        for id in ids {
+            if let Some(bp) = self.breakpoints.get_mut(*id) {
+                bp.enabled = enabled;
+            } else if let Some(wp) = self.breakpoints.get_watchpoint_mut(*id) {
+                wp.enabled = enabled;
+            } else if let Some(cp) = self.breakpoints.get_catchpoint_mut(*id) {
+                cp.enabled = enabled;
+            } else {
+                println!("No breakpoint number {}.", id);
+            }
+        }
+        self.sync_breakpoints();
+    }
+
+    fn print_breakpoints(&self) {
+        if self.breakpoints.is_empty() {
+            println!("No breakpoints.");
+            return;
+        }
+        println!(
+            "{:<4} {:<4} {:<18} {:<5} What",
+            "Num", "Enb", "Address", "Hits"
+        );
+        for bp in self.breakpoints.iter() {
+            let what = match self.debug_data.get_line_from_addr(bp.addr) {
+                // Line breakpoints are stored as file:line, which needn't be repeated
+                Some(line) if line.to_string() == bp.location => bp.location.clone(),
+                Some(line) => format!("{} at {}", bp.location, line),
+                None => bp.location.clone(),
+            };
+            println!(
+                "{:<4} {:<4} {:<#18x} {:<5} {}",
+                bp.id,
+                if bp.enabled { "y" } else { "n" },
+                bp.addr,
+                bp.hit_count,
+                what
+            );
+            if let Some(condition) = &bp.condition {
+                println!("\tstop only if {}", condition);
+            }
+            if bp.ignore_count > 0 {
+                println!("\tignore next {} hits", bp.ignore_count);
+            }
+            if bp.temporary {
+                println!("\tdeleted after the next stop");
+            }
+        }
+        for wp in self.breakpoints.watchpoints() {
+            println!(
+                "{:<4} {:<4} {:<#18x} {:<5} hw watchpoint {}",
+                wp.id,
+                if wp.enabled { "y" } else { "n" },
+                wp.addr,
+                wp.hit_count,
+                wp.expression
+            );
+        }
+        for cp in self.breakpoints.catchpoints() {
+            let names: Vec<String> = cp
+                .syscalls
+                .iter()
+                .map(|number| match syscall_name(*number) {
+                    Some(name) => name.to_string(),
+                    None => number.to_string(),
+                })
+                .collect();
+            let what = match names.len() {
+                0 => "any syscall".to_string(),
+                1 => format!("syscall \"{}\"", names[0]),
+                _ => format!("syscalls \"{}\"", names.join(", ")),
+            };
+            println!(
+                "{:<4} {:<4} {:<18} {:<5} catchpoint for {}",
+                cp.id,
+                if cp.enabled { "y" } else { "n" },
+                "",
+                cp.hit_count,
+                what
+            );
+        }
+    }
+}
+
+/// Formats a system call's return value; values from -4095 to -1 are error numbers, which are
+/// shown the way libc would report them.
+fn format_syscall_return(ret: i64) -> String {
+    if (-4095..0).contains(&ret) {
+        let errno = Errno::from_i32(-ret as i32);
+        format!("-1 {:?} ({})", errno, errno.desc())
+    } else if ret.abs() < 0x10000 {
+        ret.to_string()
+    } else {
+        format!("{:#x}", ret)
+    }
+}
+
+/// Resolves a breakpoint location: `*addr`, `line`, `function`, `file:line` or `file:function`.
+/// Returns a message instead if the location doesn't exist or could mean more than one place. A
+/// bare line number found in several files is taken to be in `default_file` if that is one of them.
+fn parse_address(
+    location: &str,
+    default_file: Option<&str>,
+    dwarfdata: &DwarfData,
+) -> Result<usize, String> {
+    if location.starts_with('*') {
+        return parse_raw_address(&location[1..]);
+    }
+
+    let (file, spec) = match location.rfind(':') {
+        Some(index) => (Some(&location[..index]), &location[index + 1..]),
+        None => (None, location),
+    };
+    let file = match file {
+        Some(file) => Some(pick_file(
+            location,
+            dwarfdata.find_files(file),
+            None,
+            format!("No source file named {}.", file),
+        )?),
+        None => None,
+    };
+
+    match spec.parse::<usize>() {
+        Ok(line_number) => {
+            let file = match file {
+                Some(file) => file,
+                None => pick_file(
+                    location,
+                    dwarfdata.files_covering_line(line_number),
+                    default_file,
+                    format!("Line {} is out of range.", line_number),
+                )?,
+            };
+            dwarfdata
+                .get_addr_for_line(Some(file), line_number)
+                .ok_or_else(|| format!("Line {} is out of range for \"{}\".", line_number, file))
+        }
+        Err(_) => {
+            let file = match file {
+                Some(file) => file,
+                None => pick_file(
+                    location,
+                    dwarfdata.files_defining_function(spec),
+                    None,
+                    format!("Function \"{}\" not defined.", spec),
+                )?,
+            };
+            dwarfdata
+                .get_addr_for_function(Some(file), spec)
+                .map(|addr| dwarfdata.skip_prologue(addr))
+                .ok_or_else(|| format!("Function \"{}\" not defined in \"{}\".", spec, file))
+        }
+    }
+}
+
+/// Spells out the file a bare line number `location` is in, so that the breakpoint keeps meaning
+/// the same line when the default file changes. Other locations are returned as they are. Says
+/// which file was picked when the line is in several and `default_file` is one of them.
+fn qualify_location(
+    location: &str,
+    default_file: Option<&str>,
+    dwarfdata: &DwarfData,
+) -> Result<String, String> {
+    let line_number = match location.parse::<usize>() {
+        Ok(line_number) => line_number,
+        Err(_) => return Ok(location.to_string()),
+    };
+    let candidates = dwarfdata.files_covering_line(line_number);
+    let ambiguous = candidates.len() > 1;
+    let file = pick_file(
+        location,
+        candidates,
+        default_file,
+        format!("Line {} is out of range.", line_number),
+    )?;
+    if ambiguous {
+        println!(
+            "Line {} is in more than one file; using {}.",
+            line_number, file
+        );
+    }
+    Ok(format!("{}:{}", file, line_number))
+}
+
+/// Parses the address in a `*addr` location, which is hex with or without the 0x.
+fn parse_raw_address(addr: &str) -> Result<usize, String> {
+    let addr_without_0x = if addr.to_lowercase().starts_with("0x") {
+        &addr[2..]
+    } else {
+        &addr
+    };
+    usize::from_str_radix(addr_without_0x, 16).map_err(|_| format!("Invalid address {}", addr))
+}
+
+/// Returns the only file in `candidates`, or `preferred` if it is among several, or an error
+/// saying the location matched none of them (`not_found`) or is ambiguous.
+fn pick_file<'a>(
+    location: &str,
+    candidates: Vec<&'a str>,
+    preferred: Option<&str>,
+    not_found: String,
+) -> Result<&'a str, String> {
+    if let Some(file) = candidates.iter().find(|file| Some(**file) == preferred) {
+        return Ok(file);
+    }
+    match candidates.len() {
+        0 => Err(not_found),
+        1 => Ok(candidates[0]),
+        _ => Err(format!(
+            "Location {} is ambiguous; it matches:\n  {}\nUse <file>:{} to pick one.",
+            location,
+            candidates.join("\n  "),
+            location.rsplit(':').next().unwrap()
+        )),
+    }
+}
+
+impl Scope for Debugger {
+    /// Enumerators are looked up when there is no variable called `name`.
+    fn variable(&self, name: &str) -> Result<Value, String> {
+        let pc = self
+            .selected_pc()
+            .map_err(|err| format!("Error reading registers: {}", err))?;
+        let var = match self.debug_data.get_variable(pc, name) {
+            Some(var) => var,
+            None => {
+                return match self.debug_data.find_enumerator(name) {
+                    Some((enum_type, value)) => Ok(Value::from_integer(enum_type.clone(), value)),
+                    None => Err(format!("No symbol \"{}\" in current context.", name)),
+                }
+            }
+        };
+        let (bytes, address) = read_location(
+            &var.location,
+            pc,
+            var.entity_type.size,
+            &self
+                .selected_stack_frame()
+                .map_err(|err| format!("Error reading registers: {}", err))?,
+        )?;
+        Ok(Value {
+            value_type: var.entity_type.clone(),
+            bytes,
+            address,
+        })
+    }
+
+    /// rip, rsp and rbp (and their aliases pc, sp and fp) are those of the selected frame, typed
+    /// as pointers the way gdb does; the other registers are plain longs.
+    fn register(&self, name: &str) -> Result<Value, String> {
+        let frame = self
+            .selected_registers()
+            .map_err(|err| format!("Error reading registers: {}", err))?;
+        let void = Type {
+            name: "void".to_string(),
+            size: 1,
+            kind: TypeKind::Void,
+        };
+        let void_pointer = Type {
+            name: "void *".to_string(),
+            size: 8,
+            kind: TypeKind::Pointer(Pointee::Void),
+        };
+        let (value, value_type) = match name {
+            "rip" | "pc" => {
+                let code = Type {
+                    name: "void ()".to_string(),
+                    kind: TypeKind::Function,
+                    ..void
+                };
+                (frame.rip as u64, Type::pointer_to(code))
+            }
+            "rsp" | "sp" => (frame.rsp as u64, void_pointer),
+            "rbp" | "fp" => (frame.rbp as u64, void_pointer),
+            _ => match self.inferior.as_ref().unwrap().register(name) {
+                Ok(Some(value)) => (value, integer_type(8, false)),
+                Ok(None) => return Err(format!("Invalid register `{}'", name)),
+                Err(err) => return Err(format!("Error reading registers: {}", err)),
+            },
+        };
+        Ok(Value::from_integer(value_type, value as i64))
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String> {
+        self.inferior
+            .as_ref()
+            .unwrap()
+            .read_bytes(addr, len)
+            .map_err(|err| format!("Cannot access memory at address {:#x}: {}", addr, err))
+    }
+
+    fn pointer_target(&self, pointer_type: &Type) -> Option<Type> {
+        self.debug_data.pointer_target(pointer_type).cloned()
+    }
+
+    fn find_type(&self, name: &str) -> Option<Type> {
+        self.debug_data.find_type(name).cloned()
+    }
+}
+
+/// A stack frame of the inferior, as variables' locations see it.
+struct StackFrame<'a> {
+    debugger: &'a Debugger,
+    registers: Registers,
+    /// Whether this is the innermost frame, whose registers are all still live
+    is_innermost: bool,
+}
+
+impl StackFrame<'_> {
+    /// Returns an address inside the code the frame is running (see `selected_pc`).
+    fn pc(&self) -> usize {
+        if self.is_innermost {
+            self.registers.rip
+        } else {
+            self.registers.rip.wrapping_sub(1)
+        }
+    }
+
+    fn function(&self) -> Option<&Function> {
+        self.debugger
+            .debug_data
+            .get_function_info_from_addr(self.pc())
+    }
+
+    fn caller(&self) -> Option<StackFrame<'_>> {
+        let inferior = self.debugger.inferior.as_ref().unwrap();
+        match self.debugger.debug_data.call_frame_info().unwind(
+            &self.registers,
+            self.is_innermost,
+            |addr| inferior.read_word(addr).ok(),
+        ) {
+            Unwind::Caller(registers) => Some(StackFrame {
+                debugger: self.debugger,
+                registers,
+                is_innermost: false,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl location::Frame for StackFrame<'_> {
+    /// Outer frames only know the registers a callee has to preserve.
+    fn register(&self, register: u16) -> Option<Vec<u8>> {
+        let inferior = self.debugger.inferior.as_ref().unwrap();
+        let value = match register {
+            6 => Some(self.registers.rbp),
+            7 => Some(self.registers.rsp),
+            16 => Some(self.registers.rip),
+            // xmm0 to xmm15
+            17..=32 if self.is_innermost => {
+                return inferior
+                    .xmm_register((register - 17) as usize)
+                    .ok()?
+                    .map(|bytes| bytes.to_vec());
+            }
+            _ if self.is_innermost => inferior
+                .register(location::register_name(register)?)
+                .ok()?
+                .map(|value| value as usize),
+            _ => CALLEE_SAVED
+                .iter()
+                .position(|saved| *saved == register)
+                .and_then(|i| self.registers.callee_saved[i]),
+        };
+        value.map(|value| value.to_le_bytes().to_vec())
+    }
+
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String> {
+        Scope::read_memory(self.debugger, addr, len)
+    }
+
+    /// The CFA is the rsp the caller is left with once the call returns.
+    fn cfa(&self) -> Option<usize> {
+        self.caller().map(|caller| caller.registers.rsp)
+    }
+
+    fn frame_base(&self) -> Option<usize> {
+        match self.function().and_then(|func| func.frame_base.as_ref()) {
+            Some(frame_base) => expression_value(frame_base, self)
+                .ok()
+                .map(|value| value as usize),
+            None => self.cfa(),
+        }
+    }
+
+    /// Right at the start of the function the register still holds its entry value. Later on,
+    /// the caller's debug info may say what it passed in the register, in terms of its own
+    /// registers and memory.
+    fn entry_value(&self, register: u16) -> Option<u64> {
+        let func = self.function()?;
+        if self.is_innermost && self.registers.rip == func.address {
+            return self.register(register).map(|bytes| {
+                let mut word = [0; 8];
+                word.copy_from_slice(&bytes[..8]);
+                u64::from_le_bytes(word)
+            });
+        }
+        let caller = self.caller()?;
+        let call_site = caller
+            .function()?
+            .call_sites
+            .iter()
+            .find(|call_site| call_site.return_address == caller.registers.rip)?;
+        let (_, value) = call_site
+            .parameters
+            .iter()
+            .find(|(parameter, _)| *parameter == register)?;
+        expression_value(value, &caller).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_address_is_hex() {
+        assert_eq!(parse_raw_address("0x401136"), Ok(0x401136));
+        assert_eq!(parse_raw_address("0X401136"), Ok(0x401136));
+        assert_eq!(parse_raw_address("401136"), Ok(0x401136));
+        assert!(parse_raw_address("main").is_err());
+        assert!(parse_raw_address("").is_err());
+    }
+
+    #[test]
+    fn pick_file_needs_one_candidate_or_the_preferred_one() {
+        let not_found = || "not found".to_string();
+        assert_eq!(pick_file("5", vec![], None, not_found()), Err(not_found()));
+        assert_eq!(pick_file("5", vec!["/a.c"], None, not_found()), Ok("/a.c"));
+        assert_eq!(
+            pick_file("5", vec!["/a.c", "/b.c"], Some("/b.c"), not_found()),
+            Ok("/b.c")
+        );
+        assert_eq!(
+            pick_file("5", vec!["/a.c"], Some("/b.c"), not_found()),
+            Ok("/a.c")
+        );
+        let ambiguous = pick_file("5", vec!["/a.c", "/b.c"], Some("/c.c"), not_found());
+        assert!(ambiguous.unwrap_err().contains("Use <file>:5"));
+    }
 }
@@ -1,12 +1,93 @@
+/// Which process `set follow-fork-mode` keeps debugging after a fork.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FollowForkMode {
+    Parent,
+    Child,
+}
+
 pub enum DebuggerCommand {
     Quit,
     Run(Vec<String>),
+    Attach(i32),
+    Detach,
     Cont,
     Back,
-    Break(String),
+    Up(usize),
+    Down(usize),
+    Frame(Option<usize>),
+    Break(String, Option<String>),
+    Tbreak(String, Option<String>),
+    Step,
+    Next,
+    Finish,
+    Record,
+    RecordStop,
+    InfoRecord,
+    ReverseStepi,
+    ReverseNext,
+    ReverseContinue,
+    Until(Option<String>),
+    Advance(String),
+    Print(String),
+    /// `display [expression]`; with no expression, shows every display now
+    Display(Option<String>),
+    Undisplay(Vec<usize>),
+    InfoDisplay,
+    Watch(String),
+    /// `catch syscall [name|number]...`
+    CatchSyscall(Vec<String>),
+    /// `handle SIGNAL keywords...`
+    Handle(String, Vec<String>),
+    Signal(String),
+    InfoSignals(Option<String>),
+    InfoThreads,
+    Thread(Option<usize>),
+    SetFollowForkMode(FollowForkMode),
+    InfoBreakpoints,
+    InfoRegisters(Vec<String>),
+    SetRegister(String, String),
+    /// `set var <lvalue> = <expression>`
+    SetVariable(String),
+    /// `x/Nfu addr`: count, format letter, unit size in bytes, address
+    Examine(usize, char, usize, String),
+    Disassemble(Option<String>),
+    List(Option<String>),
+    Directory(Vec<String>),
+    InfoLocals,
+    InfoArgs,
+    Delete(Vec<usize>),
+    Ignore(usize, usize),
+    Enable(Vec<usize>),
+    Disable(Vec<usize>),
+    /// `gcore [file]`
+    Gcore(Option<String>),
 }
 
 impl DebuggerCommand {
+    /// Returns true if the command runs or modifies the inferior, which can't be done to a core
+    /// file.
+    pub fn needs_process(&self) -> bool {
+        match self {
+            DebuggerCommand::Detach
+            | DebuggerCommand::Cont
+            | DebuggerCommand::Step
+            | DebuggerCommand::Next
+            | DebuggerCommand::Finish
+            | DebuggerCommand::Until(_)
+            | DebuggerCommand::Advance(_)
+            | DebuggerCommand::Record
+            | DebuggerCommand::ReverseStepi
+            | DebuggerCommand::ReverseNext
+            | DebuggerCommand::ReverseContinue
+            | DebuggerCommand::Watch(_)
+            | DebuggerCommand::Signal(_)
+            | DebuggerCommand::SetRegister(_, _)
+            | DebuggerCommand::SetVariable(_)
+            | DebuggerCommand::Gcore(_) => true,
+            _ => false,
+        }
+    }
+
     pub fn from_tokens(tokens: &Vec<&str>) -> Option<DebuggerCommand> {
         match tokens[0] {
             "q" | "quit" => Some(DebuggerCommand::Quit),
@@ -16,14 +97,225 @@ impl DebuggerCommand {
                     args.iter().map(|s| s.to_string()).collect(),
                 ))
             }
+            "attach" if tokens.len() == 2 => {
+                Some(DebuggerCommand::Attach(tokens[1].parse::<i32>().ok()?))
+            }
+            "detach" => Some(DebuggerCommand::Detach),
             "c" | "cont" => Some(DebuggerCommand::Cont),
             "back" => Some(DebuggerCommand::Back),
-            "b" | "break" => {
-                let address = tokens.last().unwrap().to_string();
-                Some(DebuggerCommand::Break(address))
+            "up" => Some(DebuggerCommand::Up(parse_count(&tokens[1..])?)),
+            "down" => Some(DebuggerCommand::Down(parse_count(&tokens[1..])?)),
+            "f" | "frame" => match tokens.len() {
+                1 => Some(DebuggerCommand::Frame(None)),
+                2 => Some(DebuggerCommand::Frame(Some(tokens[1].parse().ok()?))),
+                _ => None,
+            },
+            "s" | "step" => Some(DebuggerCommand::Step),
+            "n" | "next" => Some(DebuggerCommand::Next),
+            "fin" | "finish" => Some(DebuggerCommand::Finish),
+            "rec" | "record" => match tokens.get(1) {
+                None | Some(&"full") => Some(DebuggerCommand::Record),
+                Some(&"stop") => Some(DebuggerCommand::RecordStop),
+                _ => None,
+            },
+            "rsi" | "reverse-stepi" => Some(DebuggerCommand::ReverseStepi),
+            "rn" | "reverse-next" => Some(DebuggerCommand::ReverseNext),
+            "rc" | "reverse-continue" => Some(DebuggerCommand::ReverseContinue),
+            "u" | "until" if tokens.len() <= 2 => Some(DebuggerCommand::Until(
+                tokens.get(1).map(|location| location.to_string()),
+            )),
+            "advance" if tokens.len() == 2 => Some(DebuggerCommand::Advance(tokens[1].to_string())),
+            "p" | "print" if tokens.len() > 1 => {
+                Some(DebuggerCommand::Print(tokens[1..].join(" ")))
+            }
+            "b" | "break" | "tbreak" if tokens.len() > 1 => {
+                let address = tokens[1].to_string();
+                // break <location> if <condition>
+                let condition = match tokens.get(2) {
+                    Some(&"if") if tokens.len() > 3 => Some(tokens[3..].join(" ")),
+                    Some(_) => return None,
+                    None => None,
+                };
+                if tokens[0] == "tbreak" {
+                    Some(DebuggerCommand::Tbreak(address, condition))
+                } else {
+                    Some(DebuggerCommand::Break(address, condition))
+                }
+            }
+            "display" => Some(DebuggerCommand::Display(if tokens.len() > 1 {
+                Some(tokens[1..].join(" "))
+            } else {
+                None
+            })),
+            "undisplay" => Some(DebuggerCommand::Undisplay(parse_ids(&tokens[1..])?)),
+            "watch" if tokens.len() > 1 => Some(DebuggerCommand::Watch(tokens[1..].join(" "))),
+            "catch" if tokens.len() > 1 && tokens[1] == "syscall" => Some(
+                DebuggerCommand::CatchSyscall(tokens[2..].iter().map(|s| s.to_string()).collect()),
+            ),
+            "handle" if tokens.len() > 1 => Some(DebuggerCommand::Handle(
+                tokens[1].to_string(),
+                tokens[2..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "signal" if tokens.len() == 2 => Some(DebuggerCommand::Signal(tokens[1].to_string())),
+            "i" | "info" if tokens.len() > 1 => match tokens[1] {
+                "b" | "break" | "breakpoints" => Some(DebuggerCommand::InfoBreakpoints),
+                "r" | "registers" => Some(DebuggerCommand::InfoRegisters(
+                    tokens[2..]
+                        .iter()
+                        .map(|s| s.trim_start_matches('$').to_string())
+                        .collect(),
+                )),
+                "threads" => Some(DebuggerCommand::InfoThreads),
+                "record" => Some(DebuggerCommand::InfoRecord),
+                "locals" => Some(DebuggerCommand::InfoLocals),
+                "display" => Some(DebuggerCommand::InfoDisplay),
+                "args" => Some(DebuggerCommand::InfoArgs),
+                "signals" | "handle" if tokens.len() <= 3 => Some(DebuggerCommand::InfoSignals(
+                    tokens.get(2).map(|signal| signal.to_string()),
+                )),
+                _ => None,
+            },
+            "thread" => match tokens.len() {
+                1 => Some(DebuggerCommand::Thread(None)),
+                2 => Some(DebuggerCommand::Thread(Some(tokens[1].parse().ok()?))),
+                _ => None,
+            },
+            "set" if tokens.len() == 3 && tokens[1] == "follow-fork-mode" => {
+                Some(DebuggerCommand::SetFollowForkMode(match tokens[2] {
+                    "parent" => FollowForkMode::Parent,
+                    "child" => FollowForkMode::Child,
+                    _ => return None,
+                }))
+            }
+            "set" if tokens.len() > 1 && tokens[1].starts_with('$') => {
+                let assignment = tokens[1..].join(" ");
+                let mut sides = assignment[1..].splitn(2, '=');
+                let register = sides.next()?.trim();
+                let value = sides.next()?.trim();
+                if register.is_empty() || value.is_empty() {
+                    return None;
+                }
+                Some(DebuggerCommand::SetRegister(
+                    register.to_string(),
+                    value.to_string(),
+                ))
+            }
+            "set" if tokens.len() > 2 && (tokens[1] == "var" || tokens[1] == "variable") => {
+                Some(DebuggerCommand::SetVariable(tokens[2..].join(" ")))
+            }
+            // Like gdb, `set` followed by an assignment that isn't a setting is a `set var`
+            "set" if tokens.len() > 1 && tokens[1..].iter().any(|token| token.contains('=')) => {
+                Some(DebuggerCommand::SetVariable(tokens[1..].join(" ")))
+            }
+            examine if (examine == "x" || examine.starts_with("x/")) && tokens.len() == 2 => {
+                let (count, format, size) = parse_examine_format(&examine[1..])?;
+                Some(DebuggerCommand::Examine(
+                    count,
+                    format,
+                    size,
+                    tokens[1].to_string(),
+                ))
             }
+            "disas" | "disassemble" if tokens.len() <= 2 => Some(DebuggerCommand::Disassemble(
+                tokens.get(1).map(|location| location.to_string()),
+            )),
+            "l" | "list" if tokens.len() <= 2 => Some(DebuggerCommand::List(
+                tokens.get(1).map(|location| location.to_string()),
+            )),
+            "dir" | "directory" => Some(DebuggerCommand::Directory(
+                tokens[1..].iter().map(|s| s.to_string()).collect(),
+            )),
+            "d" | "delete" => Some(DebuggerCommand::Delete(parse_ids(&tokens[1..])?)),
+            "ignore" if tokens.len() == 3 => Some(DebuggerCommand::Ignore(
+                tokens[1].parse().ok()?,
+                tokens[2].parse().ok()?,
+            )),
+            "enable" => Some(DebuggerCommand::Enable(parse_ids(&tokens[1..])?)),
+            "disable" => Some(DebuggerCommand::Disable(parse_ids(&tokens[1..])?)),
+            "gcore" if tokens.len() <= 2 => Some(DebuggerCommand::Gcore(
+                tokens.get(1).map(|file| file.to_string()),
+            )),
             // Default case:
             _ => None,
         }
     }
 }
+
+/// Parses a list of breakpoint numbers, returning None if any of them isn't a number.
+fn parse_ids(tokens: &[&str]) -> Option<Vec<usize>> {
+    tokens
+        .iter()
+        .map(|token| token.parse::<usize>().ok())
+        .collect()
+}
+
+/// Parses the optional count taken by `up` and `down`, which defaults to 1.
+fn parse_count(tokens: &[&str]) -> Option<usize> {
+    match tokens {
+        [] => Some(1),
+        [count] => count.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parses the `/Nfu` suffix of `x`: an optional count, then format and unit size letters in
+/// either order. Defaults to one hex word. Strings are read a byte at a time, and the unit size
+/// means nothing for instructions.
+fn parse_examine_format(suffix: &str) -> Option<(usize, char, usize)> {
+    let (mut count, mut format, mut size) = (1, 'x', None);
+    if suffix.is_empty() {
+        return Some((count, format, 4));
+    }
+    let spec = &suffix[1..];
+    let digits = spec.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        count = spec[..digits].parse().ok()?;
+    }
+    for letter in spec[digits..].chars() {
+        match letter {
+            'x' | 'd' | 'c' | 's' | 'i' => format = letter,
+            'b' => size = Some(1),
+            'h' => size = Some(2),
+            'w' => size = Some(4),
+            'g' => size = Some(8),
+            _ => return None,
+        }
+    }
+    let size = match format {
+        'c' | 's' | 'i' => 1,
+        _ => size.unwrap_or(4),
+    };
+    Some((count, format, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examine_format_defaults_to_one_hex_word() {
+        assert_eq!(parse_examine_format(""), Some((1, 'x', 4)));
+        assert_eq!(parse_examine_format("/"), Some((1, 'x', 4)));
+    }
+
+    #[test]
+    fn examine_format_count_format_and_size() {
+        assert_eq!(parse_examine_format("/4xg"), Some((4, 'x', 8)));
+        assert_eq!(parse_examine_format("/gx"), Some((1, 'x', 8)));
+        assert_eq!(parse_examine_format("/10db"), Some((10, 'd', 1)));
+        assert_eq!(parse_examine_format("/2h"), Some((2, 'x', 2)));
+    }
+
+    #[test]
+    fn examine_format_strings_and_instructions_are_bytes() {
+        assert_eq!(parse_examine_format("/s"), Some((1, 's', 1)));
+        assert_eq!(parse_examine_format("/3ig"), Some((3, 'i', 1)));
+        assert_eq!(parse_examine_format("/8c"), Some((8, 'c', 1)));
+    }
+
+    #[test]
+    fn examine_format_rejects_unknown_letters() {
+        assert_eq!(parse_examine_format("/4q"), None);
+        assert_eq!(parse_examine_format("/x4"), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! x86-64 disassembly of the inferior's code, using iced-x86 to decode instructions.
+
+use iced_x86::{
+    Decoder, DecoderOptions, Formatter, Instruction, InstructionInfoFactory, IntelFormatter,
+    Mnemonic, OpAccess, OpKind, Register,
+};
+
+/// The DF bit of rflags, which makes string instructions step downwards
+const DIRECTION_FLAG: u64 = 1 << 10;
+
+/// One decoded instruction.
+#[derive(Debug, Clone)]
+pub struct DecodedInstruction {
+    pub addr: usize,
+    pub len: usize,
+    /// The instruction in Intel syntax, e.g. `mov rbp,rsp`
+    pub text: String,
+    /// Where the instruction jumps or calls to, if it is a direct branch
+    pub branch_target: Option<usize>,
+}
+
+/// Decodes the instructions in `code`, which was read from address `addr`, stopping after
+/// `max_count` instructions or when the bytes run out. An instruction cut off by the end of
+/// `code` is not returned.
+pub fn disassemble(code: &[u8], addr: usize, max_count: usize) -> Vec<DecodedInstruction> {
+    let mut decoder = Decoder::with_ip(64, code, addr as u64, DecoderOptions::NONE);
+    let mut formatter = IntelFormatter::new();
+    // Make the output look like gdb's
+    formatter.options_mut().set_hex_prefix("0x");
+    formatter.options_mut().set_hex_suffix("");
+    formatter.options_mut().set_uppercase_hex(false);
+    formatter.options_mut().set_branch_leading_zeros(false);
+    formatter
+        .options_mut()
+        .set_space_after_operand_separator(true);
+    formatter.options_mut().set_first_operand_char_index(7);
+    let mut instruction = Instruction::default();
+    let mut decoded = Vec::new();
+    while decoder.can_decode() && decoded.len() < max_count {
+        let offset = decoder.position();
+        decoder.decode_out(&mut instruction);
+        if instruction.is_invalid() && offset + instruction.len() >= code.len() {
+            break;
+        }
+        let mut text = String::new();
+        if instruction.is_invalid() {
+            text.push_str("(bad)");
+        } else {
+            formatter.format(&instruction, &mut text);
+        }
+        let branch_target = instruction.near_branch_target() as usize;
+        decoded.push(DecodedInstruction {
+            addr: instruction.ip() as usize,
+            len: instruction.len(),
+            text,
+            branch_target: if branch_target != 0 {
+                Some(branch_target)
+            } else {
+                None
+            },
+        });
+    }
+    decoded
+}
+
+/// How an instruction moves between functions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Call,
+    Return,
+    /// A system call, whose effects on memory can't be known
+    Syscall,
+    Other,
+}
+
+/// What executing one instruction will do, as far as undoing it is concerned.
+#[derive(Debug, Clone)]
+pub struct InstructionEffects {
+    pub flow: Flow,
+    /// The `(address, length)` of every piece of memory the instruction may write, including
+    /// the stack slots written by push and call
+    pub writes: Vec<(usize, usize)>,
+}
+
+/// Works out the effects of the instruction at the start of `code`, which is about to be executed
+/// with registers `regs`. Returns None if the bytes aren't a valid instruction.
+pub fn instruction_effects(
+    code: &[u8],
+    regs: &libc::user_regs_struct,
+) -> Option<InstructionEffects> {
+    let mut decoder = Decoder::with_ip(64, code, regs.rip, DecoderOptions::NONE);
+    let instruction = decoder.decode();
+    if instruction.is_invalid() {
+        return None;
+    }
+    let flow = match instruction.mnemonic() {
+        Mnemonic::Call => Flow::Call,
+        Mnemonic::Ret => Flow::Return,
+        Mnemonic::Syscall | Mnemonic::Sysenter => Flow::Syscall,
+        _ => Flow::Other,
+    };
+    // A rep-prefixed stos, movs or ins writes rcx elements before it finishes, only the first of
+    // which is its memory operand
+    let repeat_count = if instruction.has_rep_prefix() || instruction.has_repne_prefix() {
+        match instruction.op0_kind() {
+            OpKind::MemoryESRDI => Some(regs.rcx as usize),
+            // With a 32-bit address size the count is in ecx
+            OpKind::MemoryESEDI => Some((regs.rcx & 0xffff_ffff) as usize),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let mut factory = InstructionInfoFactory::new();
+    let writes = factory
+        .info(&instruction)
+        .used_memory()
+        .iter()
+        .filter(|memory| match memory.access() {
+            OpAccess::Write
+            | OpAccess::CondWrite
+            | OpAccess::ReadWrite
+            | OpAccess::ReadCondWrite => true,
+            _ => false,
+        })
+        .filter_map(|memory| {
+            let addr =
+                memory.virtual_address(0, |register, _, _| register_value(regs, register))?;
+            let addr = addr as usize;
+            let count = match repeat_count {
+                Some(count) => count,
+                None => return Some((addr, memory.memory_size().size())),
+            };
+            // The access itself has no size, since it depends on the count
+            let size = instruction.memory_size().size();
+            if count == 0 {
+                return None;
+            }
+            let len = count.checked_mul(size)?;
+            // With the direction flag set, the elements are written downwards from rdi
+            if regs.eflags & DIRECTION_FLAG != 0 {
+                Some((addr.wrapping_sub(len - size), len))
+            } else {
+                Some((addr, len))
+            }
+        })
+        .collect();
+    Some(InstructionEffects { flow, writes })
+}
+
+/// Returns the value of a general purpose register in `regs`, or the base address of
+/// a segment register, as needed to compute the address of a memory operand.
+fn register_value(regs: &libc::user_regs_struct, register: Register) -> Option<u64> {
+    let value = match register.full_register() {
+        Register::RAX => regs.rax,
+        Register::RBX => regs.rbx,
+        Register::RCX => regs.rcx,
+        Register::RDX => regs.rdx,
+        Register::RSI => regs.rsi,
+        Register::RDI => regs.rdi,
+        Register::RBP => regs.rbp,
+        Register::RSP => regs.rsp,
+        Register::R8 => regs.r8,
+        Register::R9 => regs.r9,
+        Register::R10 => regs.r10,
+        Register::R11 => regs.r11,
+        Register::R12 => regs.r12,
+        Register::R13 => regs.r13,
+        Register::R14 => regs.r14,
+        Register::R15 => regs.r15,
+        Register::RIP => regs.rip,
+        Register::FS => regs.fs_base,
+        Register::GS => regs.gs_base,
+        // The other segments are flat in 64-bit mode
+        Register::ES | Register::CS | Register::SS | Register::DS => 0,
+        _ => return None,
+    };
+    // Addresses can also be computed from 32-bit registers, which are the low half of the full one
+    if register.size() == 4 {
+        Some(value & 0xffff_ffff)
+    } else {
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regs(rdi: u64, rcx: u64, eflags: u64) -> libc::user_regs_struct {
+        let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = 0x1000;
+        regs.rdi = rdi;
+        regs.rcx = rcx;
+        regs.eflags = eflags;
+        regs
+    }
+
+    #[test]
+    fn rep_stos_writes_every_element() {
+        // rep stos qword ptr [rdi], rax
+        let effects = instruction_effects(&[0xf3, 0x48, 0xab], &regs(0x2000, 4, 0)).unwrap();
+        assert_eq!(effects.writes, vec![(0x2000, 32)]);
+        // Downwards when the direction flag is set
+        let effects =
+            instruction_effects(&[0xf3, 0x48, 0xab], &regs(0x2000, 4, DIRECTION_FLAG)).unwrap();
+        assert_eq!(effects.writes, vec![(0x2000 - 24, 32)]);
+        // Nothing at all with a zero count
+        let effects = instruction_effects(&[0xf3, 0x48, 0xab], &regs(0x2000, 0, 0)).unwrap();
+        assert!(effects.writes.is_empty());
+    }
+
+    #[test]
+    fn plain_stos_writes_one_element() {
+        // stos byte ptr [rdi], al
+        let effects = instruction_effects(&[0xaa], &regs(0x2000, 4, 0)).unwrap();
+        assert_eq!(effects.writes, vec![(0x2000, 1)]);
+    }
+
+    #[test]
+    fn flow() {
+        let flow = |code: &[u8]| instruction_effects(code, &regs(0, 0, 0)).unwrap().flow;
+        assert_eq!(flow(&[0x0f, 0x05]), Flow::Syscall);
+        assert_eq!(flow(&[0xc3]), Flow::Return);
+        assert_eq!(flow(&[0xe8, 0, 0, 0, 0]), Flow::Call);
+        assert_eq!(flow(&[0x90]), Flow::Other);
+    }
+}
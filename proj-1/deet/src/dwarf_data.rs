@@ -1,6 +1,8 @@
 use crate::gimli_wrapper;
+use crate::unwind::CallFrameInfo;
 use addr2line::Context;
 use object::Object;
+use std::collections::HashSet;
 use std::convert::TryInto;
 use std::{fmt, fs};
 
@@ -12,7 +14,12 @@ pub enum Error {
 
 pub struct DwarfData {
     files: Vec<File>,
+    /// Every type in the program, indexed by TypeId
+    types: Vec<Type>,
+    /// The address of every row of the line table, for telling when a step reaches a new line
+    line_starts: HashSet<usize>,
     addr2line: Context<addr2line::gimli::EndianRcSlice<addr2line::gimli::RunTimeEndian>>,
+    call_frame_info: CallFrameInfo,
 }
 
 impl fmt::Debug for DwarfData {
@@ -38,20 +45,98 @@ impl DwarfData {
         } else {
             gimli::RunTimeEndian::Big
         };
+        let mut types = Vec::new();
+        let files = gimli_wrapper::load_file(&object, endian, &mut types)?;
+        let line_starts = files
+            .iter()
+            .flat_map(|file| file.lines.iter().map(|line| line.address))
+            .collect();
         Ok(DwarfData {
-            files: gimli_wrapper::load_file(&object, endian)?,
+            files,
+            types,
+            line_starts,
             addr2line: Context::new(&object).or_else(|e| Err(gimli_wrapper::Error::from(e)))?,
+            call_frame_info: CallFrameInfo::from_object(&object, 0),
         })
     }
 
-    #[allow(dead_code)]
-    fn get_target_file(&self, file: &str) -> Option<&File> {
-        self.files.iter().find(|f| {
-            f.name == file || (!file.contains("/") && f.name.ends_with(&format!("/{}", file)))
+    /// Returns the type a pointer of type `pointer_type` points to, or None if it is a void
+    /// pointer (or not a pointer at all).
+    pub fn pointer_target<'a>(&'a self, pointer_type: &'a Type) -> Option<&'a Type> {
+        match &pointer_type.resolved().kind {
+            TypeKind::Pointer(Pointee::Id(id)) => self.types.get(*id),
+            TypeKind::Pointer(Pointee::Type(target)) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Returns the enumerator called `name`, along with the enum it belongs to.
+    pub fn find_enumerator(&self, name: &str) -> Option<(&Type, i64)> {
+        self.types.iter().find_map(|t| match &t.kind {
+            TypeKind::Enum(enumerators) => enumerators
+                .iter()
+                .find(|(enumerator, _)| enumerator == name)
+                .map(|(_, value)| (t, *value)),
+            _ => None,
         })
     }
 
-    #[allow(dead_code)]
+    /// Returns the type called `name`, as C would write it (e.g. `struct node` or `size_t`).
+    pub fn find_type(&self, name: &str) -> Option<&Type> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// Returns the target's own CFI tables, for unwinding frames in the target.
+    pub fn call_frame_info(&self) -> &CallFrameInfo {
+        &self.call_frame_info
+    }
+
+    fn get_target_file(&self, file: &str) -> Option<&File> {
+        self.files.iter().find(|f| file_matches(&f.name, file))
+    }
+
+    /// Returns the names of all compilation units `file` could refer to. A relative name such as
+    /// `foo.c` or `src/foo.c` matches any unit whose path ends with it.
+    pub fn find_files(&self, file: &str) -> Vec<&str> {
+        match self.files.iter().find(|f| f.name == file) {
+            Some(f) => vec![f.name.as_str()],
+            None => self
+                .files
+                .iter()
+                .filter(|f| file_matches(&f.name, file))
+                .map(|f| f.name.as_str())
+                .collect(),
+        }
+    }
+
+    /// Returns the names of the compilation units that define (not just declare) a function
+    /// called `func_name`.
+    pub fn files_defining_function(&self, func_name: &str) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|f| {
+                f.functions
+                    .iter()
+                    .any(|func| func.name == func_name && func.address != 0)
+            })
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+
+    /// Returns the names of the compilation units whose line table spans `line_number`.
+    pub fn files_covering_line(&self, line_number: usize) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|f| {
+                f.lines.iter().any(|line| line.number <= line_number)
+                    && f.lines.iter().any(|line| line.number >= line_number)
+            })
+            .map(|f| f.name.as_str())
+            .collect()
+    }
+
+    /// Returns the address of the code for `line_number`, or for the closest line after it that
+    /// has code, in `file` (or the first compilation unit).
     pub fn get_addr_for_line(&self, file: Option<&str>, line_number: usize) -> Option<usize> {
         let target_file = match file {
             Some(filename) => self.get_target_file(filename)?,
@@ -61,24 +146,28 @@ impl DwarfData {
             target_file
                 .lines
                 .iter()
-                .find(|line| line.number >= line_number)?
+                .filter(|line| line.number >= line_number)
+                .min_by_key(|line| (line.number, line.address))?
                 .address,
         )
     }
 
-    #[allow(dead_code)]
     pub fn get_addr_for_function(&self, file: Option<&str>, func_name: &str) -> Option<usize> {
         match file {
             Some(filename) => Some(
                 self.get_target_file(filename)?
                     .functions
                     .iter()
-                    .find(|func| func.name == func_name)?
+                    .find(|func| func.name == func_name && func.address != 0)?
                     .address,
             ),
             None => {
                 for file in &self.files {
-                    if let Some(func) = file.functions.iter().find(|func| func.name == func_name) {
+                    if let Some(func) = file
+                        .functions
+                        .iter()
+                        .find(|func| func.name == func_name && func.address != 0)
+                    {
                         return Some(func.address);
                     }
                 }
@@ -87,7 +176,6 @@ impl DwarfData {
         }
     }
 
-    #[allow(dead_code)]
     pub fn get_line_from_addr(&self, curr_addr: usize) -> Option<Line> {
         let location = self
             .addr2line
@@ -100,7 +188,6 @@ impl DwarfData {
         })
     }
 
-    #[allow(dead_code)]
     pub fn get_function_from_addr(&self, curr_addr: usize) -> Option<String> {
         let frame = self
             .addr2line
@@ -111,7 +198,61 @@ impl DwarfData {
         Some(frame.function?.raw_name().ok()?.to_string())
     }
 
-    #[allow(dead_code)]
+    pub fn get_function_info_from_addr(&self, curr_addr: usize) -> Option<&Function> {
+        self.files
+            .iter()
+            .flat_map(|file| file.functions.iter())
+            .find(|func| func.address <= curr_addr && curr_addr < func.address + func.text_length)
+    }
+
+    /// Returns the address just past the prologue of the function starting at `func_addr`, where
+    /// the frame has been set up and parameters can be read. That is the address the line table
+    /// flags as the end of the prologue if there is one, and otherwise the second line-table row
+    /// of the function. If neither exists, `func_addr` itself is returned.
+    pub fn skip_prologue(&self, func_addr: usize) -> usize {
+        let func = match self.get_function_info_from_addr(func_addr) {
+            Some(func) if func.address == func_addr => func,
+            _ => return func_addr,
+        };
+        let in_body =
+            |addr: &usize| func.address < *addr && *addr < func.address + func.text_length;
+        let prologue_end = self
+            .files
+            .iter()
+            .flat_map(|file| file.prologue_ends.iter())
+            .copied()
+            .filter(in_body)
+            .min();
+        let second_row = self
+            .files
+            .iter()
+            .flat_map(|file| file.lines.iter())
+            .map(|line| line.address)
+            .filter(in_body)
+            .min();
+        prologue_end.or(second_row).unwrap_or(func_addr)
+    }
+
+    /// Looks up a variable visible from `curr_addr`: a local or parameter of the enclosing function
+    /// if there is one with that name, otherwise a global.
+    pub fn get_variable(&self, curr_addr: usize, name: &str) -> Option<&Variable> {
+        if let Some(func) = self.get_function_info_from_addr(curr_addr) {
+            if let Some(var) = func.variables.iter().find(|var| var.name == name) {
+                return Some(var);
+            }
+        }
+        self.files
+            .iter()
+            .flat_map(|file| file.global_variables.iter())
+            .find(|var| var.name == name)
+    }
+
+    /// Returns true if `curr_addr` begins a row of the line table, i.e. it is the first
+    /// instruction of a source line. If no line table was loaded at all, every address counts.
+    pub fn is_line_start(&self, curr_addr: usize) -> bool {
+        self.line_starts.is_empty() || self.line_starts.contains(&curr_addr)
+    }
+
     pub fn print(&self) {
         for file in &self.files {
             println!("------");
@@ -148,32 +289,275 @@ impl DwarfData {
     }
 }
 
-#[derive(Debug, Clone, Default)]
+/// Identifies a type in the table `DwarfData::pointer_target` looks types up in. Pointers refer to
+/// their targets this way, so that a struct that points to itself doesn't contain itself.
+pub type TypeId = usize;
+
+/// How the bytes of a base type are to be read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Signed,
+    Unsigned,
+    SignedChar,
+    UnsignedChar,
+    Boolean,
+    Float,
+}
+
+/// A member of a struct or union.
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub name: String,
+    /// Offset of the member from the start of the struct
+    pub offset: usize,
+    pub member_type: Type,
+}
+
+/// What a pointer points to.
+#[derive(Debug, Clone)]
+pub enum Pointee {
+    Void,
+    /// A type in the DWARF type table
+    Id(TypeId),
+    /// A type that isn't in the table, such as the target of `&var` in an expression
+    Type(Box<Type>),
+}
+
+#[derive(Debug, Clone)]
+pub enum TypeKind {
+    Base(Encoding),
+    Pointer(Pointee),
+    Struct(Vec<Member>),
+    Union(Vec<Member>),
+    /// An array of the given element type, and its length if known
+    Array(Box<Type>, Option<usize>),
+    /// An enum and its enumerators
+    Enum(Vec<(String, i64)>),
+    Typedef(Box<Type>),
+    Const(Box<Type>),
+    Volatile(Box<Type>),
+    Function,
+    Void,
+}
+
+/// A C type, as described by the DWARF info. `name` is the name C would write it as (e.g.
+/// `struct node *` or `int [10]`).
+#[derive(Debug, Clone)]
 pub struct Type {
     pub name: String,
     pub size: usize,
+    pub kind: TypeKind,
 }
 
+/// Arrays longer than this are cut short when printed.
+const MAX_ARRAY_ELEMENTS: usize = 200;
+
 impl Type {
-    pub fn new(name: String, size: usize) -> Self {
+    pub fn base(name: &str, size: usize, encoding: Encoding) -> Self {
+        Type {
+            name: name.to_string(),
+            size,
+            kind: TypeKind::Base(encoding),
+        }
+    }
+
+    /// Returns the type of a pointer to `target`.
+    pub fn pointer_to(target: Type) -> Self {
+        let name = match target.kind {
+            // int (*)[5] and int (*)(int)
+            TypeKind::Array(..) => target.name.replacen(" [", " (*)[", 1),
+            TypeKind::Function => target.name.replacen(" (", " (*)(", 1),
+            _ if target.name.ends_with('*') => format!("{}*", target.name),
+            _ => format!("{} *", target.name),
+        };
         Type {
-            name: name,
-            size: size,
+            name,
+            size: 8,
+            kind: TypeKind::Pointer(Pointee::Type(Box::new(target))),
+        }
+    }
+
+    /// Returns the type with typedefs and const/volatile qualifiers stripped off, which is what
+    /// decides how a value of the type is laid out.
+    pub fn resolved(&self) -> &Type {
+        match &self.kind {
+            TypeKind::Typedef(target) | TypeKind::Const(target) | TypeKind::Volatile(target) => {
+                target.resolved()
+            }
+            _ => self,
+        }
+    }
+
+    /// Interprets a value of this type as an integer, for comparisons. Floating point values are
+    /// truncated.
+    pub fn value_as_i64(&self, bytes: &[u8]) -> i64 {
+        let (unsigned, signed) = raw_integer(bytes);
+        match self.resolved().kind {
+            TypeKind::Base(Encoding::Float) if bytes.len() == 4 => {
+                f32::from_bits(unsigned as u32) as i64
+            }
+            TypeKind::Base(Encoding::Float) if bytes.len() == 8 => f64::from_bits(unsigned) as i64,
+            TypeKind::Base(Encoding::Unsigned)
+            | TypeKind::Base(Encoding::UnsignedChar)
+            | TypeKind::Base(Encoding::Boolean)
+            | TypeKind::Pointer(_) => unsigned as i64,
+            _ => signed,
         }
     }
+
+    /// Formats a value of this type from its raw (little-endian) bytes, the way gdb would: structs
+    /// and arrays in braces, enums by name and pointers as addresses.
+    pub fn format_value(&self, bytes: &[u8]) -> String {
+        let (unsigned, signed) = raw_integer(bytes);
+        match &self.resolved().kind {
+            TypeKind::Pointer(_) => format!("{:#x}", unsigned),
+            TypeKind::Base(Encoding::Boolean) => format!("{}", unsigned != 0),
+            TypeKind::Base(Encoding::SignedChar) if bytes.len() == 1 => {
+                format!("{} {}", signed, char_literal(bytes[0]))
+            }
+            TypeKind::Base(Encoding::UnsignedChar) if bytes.len() == 1 => {
+                format!("{} {}", unsigned, char_literal(bytes[0]))
+            }
+            TypeKind::Base(Encoding::Float) if bytes.len() == 4 => {
+                format!("{}", f32::from_bits(unsigned as u32))
+            }
+            TypeKind::Base(Encoding::Float) if bytes.len() == 8 => {
+                format!("{}", f64::from_bits(unsigned))
+            }
+            TypeKind::Base(Encoding::Unsigned) | TypeKind::Base(Encoding::UnsignedChar)
+                if [1, 2, 4, 8].contains(&bytes.len()) =>
+            {
+                format!("{}", unsigned)
+            }
+            TypeKind::Base(_) if [1, 2, 4, 8].contains(&bytes.len()) => format!("{}", signed),
+            TypeKind::Enum(enumerators) => {
+                match enumerators.iter().find(|(_, value)| *value == signed) {
+                    Some((name, _)) => name.clone(),
+                    None => format!("{}", signed),
+                }
+            }
+            TypeKind::Struct(members) | TypeKind::Union(members) => {
+                let fields: Vec<String> = members
+                    .iter()
+                    .map(|member| {
+                        let start = member.offset.min(bytes.len());
+                        let end = (member.offset + member.member_type.size).min(bytes.len());
+                        format!(
+                            "{} = {}",
+                            member.name,
+                            member.member_type.format_value(&bytes[start..end])
+                        )
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            TypeKind::Array(element, _) => {
+                if let TypeKind::Base(Encoding::SignedChar)
+                | TypeKind::Base(Encoding::UnsignedChar) = element.resolved().kind
+                {
+                    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+                    return format!("{:?}", String::from_utf8_lossy(&bytes[..len]));
+                }
+                if element.size == 0 {
+                    return "{}".to_string();
+                }
+                let mut values: Vec<String> = bytes
+                    .chunks(element.size)
+                    .take(MAX_ARRAY_ELEMENTS)
+                    .map(|chunk| element.format_value(chunk))
+                    .collect();
+                if bytes.len() / element.size > MAX_ARRAY_ELEMENTS {
+                    values.push("...".to_string());
+                }
+                format!("{{{}}}", values.join(", "))
+            }
+            TypeKind::Void => "void".to_string(),
+            _ => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+                format!("<{} bytes: {}>", bytes.len(), hex.join(" "))
+            }
+        }
+    }
+
+    /// Returns the member called `name` if this is a struct or union.
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        match &self.resolved().kind {
+            TypeKind::Struct(members) | TypeKind::Union(members) => {
+                members.iter().find(|member| member.name == name)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads up to 8 little-endian bytes as an integer, returning it both zero- and sign-extended.
+fn raw_integer(bytes: &[u8]) -> (u64, i64) {
+    let mut raw = [0u8; 8];
+    let len = bytes.len().min(8);
+    raw[..len].copy_from_slice(&bytes[..len]);
+    let unsigned = u64::from_le_bytes(raw);
+    if len == 0 {
+        return (0, 0);
+    }
+    let shift = 64 - 8 * len as u32;
+    (unsigned, ((unsigned << shift) as i64) >> shift)
+}
+
+/// Formats `byte` as a C character constant, e.g. `'a'`, `'\n'` or `'\351'`.
+fn char_literal(byte: u8) -> String {
+    match byte {
+        b'\'' => "'\\''".to_string(),
+        b'\\' => "'\\\\'".to_string(),
+        b'\n' => "'\\n'".to_string(),
+        b'\t' => "'\\t'".to_string(),
+        b'\r' => "'\\r'".to_string(),
+        0x07 => "'\\a'".to_string(),
+        0x08 => "'\\b'".to_string(),
+        0x0b => "'\\v'".to_string(),
+        0x0c => "'\\f'".to_string(),
+        0x20..=0x7e => format!("'{}'", byte as char),
+        _ => format!("'\\{:03o}'", byte),
+    }
+}
+
+/// Returns true if `name` can be used to refer to the compilation unit at `path`: either the
+/// path itself, or a trailing part of it such as `foo.c` or `src/foo.c`.
+fn file_matches(path: &str, name: &str) -> bool {
+    path == name || (!name.starts_with('/') && path.ends_with(&format!("/{}", name)))
+}
+
+/// A DWARF expression, as found in location descriptions, frame bases and call site parameters.
+#[derive(Debug, Clone)]
+pub struct DwarfExpression {
+    pub bytes: Vec<u8>,
+    pub encoding: gimli::Encoding,
 }
 
 #[derive(Clone)]
 pub enum Location {
     Address(usize),
     FramePointerOffset(isize),
+    /// Anything more involved than the two above, e.g. a register or a value made of pieces
+    Expression(DwarfExpression),
+    /// A location list: where the variable is while the pc is in each range `[start, end)`
+    List(Vec<(usize, usize, DwarfExpression)>),
+    /// The value itself, for variables the compiler folded into a constant
+    Constant(Vec<u8>),
+    /// The compiler kept nothing of the variable
+    OptimizedOut,
 }
 
 impl fmt::Display for Location {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match *self {
+        match self {
             Location::Address(addr) => write!(f, "Address({:#x})", addr),
             Location::FramePointerOffset(offset) => write!(f, "FramePointerOffset({})", offset),
+            Location::Expression(expression) => {
+                write!(f, "Expression({} bytes)", expression.bytes.len())
+            }
+            Location::List(entries) => write!(f, "List({} entries)", entries.len()),
+            Location::Constant(bytes) => write!(f, "Constant({:02x?})", bytes),
+            Location::OptimizedOut => write!(f, "OptimizedOut"),
         }
     }
 }
@@ -191,6 +575,7 @@ pub struct Variable {
     pub entity_type: Type,
     pub location: Location,
     pub line_number: usize, // Line number in source file
+    pub is_parameter: bool, // True for function parameters, false for locals and globals
 }
 
 #[derive(Debug, Default, Clone)]
@@ -200,6 +585,20 @@ pub struct Function {
     pub text_length: usize,
     pub line_number: usize, // Line number in source file
     pub variables: Vec<Variable>,
+    /// What DW_OP_fbreg offsets are relative to (DW_AT_frame_base)
+    pub frame_base: Option<DwarfExpression>,
+    pub call_sites: Vec<CallSite>,
+}
+
+/// A call the function makes, with what is known about the arguments it passes. This is how the
+/// callee's parameters can still be found after their registers have been reused.
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    /// The address the call returns to
+    pub return_address: usize,
+    /// The DWARF register each argument is passed in, and an expression for its value, which is
+    /// evaluated in the caller's frame
+    pub parameters: Vec<(u16, DwarfExpression)>,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -208,6 +607,7 @@ pub struct File {
     pub global_variables: Vec<Variable>,
     pub functions: Vec<Function>,
     pub lines: Vec<Line>,
+    pub prologue_ends: Vec<usize>, // Addresses the line table flags as the end of a prologue
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -223,4 +623,117 @@ impl fmt::Display for Line {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_matches_full_path_and_trailing_components() {
+        let path = "/deet/samples/foo.c";
+        assert!(file_matches(path, "/deet/samples/foo.c"));
+        assert!(file_matches(path, "foo.c"));
+        assert!(file_matches(path, "samples/foo.c"));
+    }
+
+    #[test]
+    fn file_matches_only_whole_components() {
+        let path = "/deet/samples/foo.c";
+        assert!(!file_matches(path, "o.c"));
+        assert!(!file_matches(path, "les/foo.c"));
+        assert!(!file_matches(path, "/samples/foo.c"));
+        assert!(!file_matches(path, "bar.c"));
+    }
+
+    #[test]
+    fn raw_integer_extends_sign() {
+        assert_eq!(raw_integer(&[]), (0, 0));
+        assert_eq!(raw_integer(&[0xff]), (0xff, -1));
+        assert_eq!(raw_integer(&[0x7f]), (0x7f, 127));
+        assert_eq!(raw_integer(&[0xfe, 0xff]), (0xfffe, -2));
+        assert_eq!(raw_integer(&(-5i32).to_le_bytes()), (0xffff_fffb, -5));
+        assert_eq!(raw_integer(&u64::MAX.to_le_bytes()), (u64::MAX, -1));
+        // Anything past 8 bytes is ignored
+        assert_eq!(raw_integer(&[1, 0, 0, 0, 0, 0, 0, 0, 0xff]), (1, 1));
+    }
 
+    #[test]
+    fn format_base_values() {
+        let int = Type::base("int", 4, Encoding::Signed);
+        assert_eq!(int.format_value(&(-42i32).to_le_bytes()), "-42");
+        let uint = Type::base("unsigned int", 4, Encoding::Unsigned);
+        assert_eq!(uint.format_value(&(-1i32).to_le_bytes()), "4294967295");
+        let c = Type::base("char", 1, Encoding::SignedChar);
+        assert_eq!(c.format_value(b"A"), "65 'A'");
+        assert_eq!(c.format_value(&[5]), "5 '\\005'");
+        assert_eq!(c.format_value(&[0xe9]), "-23 '\\351'");
+        assert_eq!(c.format_value(b"\n"), "10 '\\n'");
+        let uc = Type::base("unsigned char", 1, Encoding::UnsignedChar);
+        assert_eq!(uc.format_value(&[0xe9]), "233 '\\351'");
+        assert_eq!(uc.format_value(&[0]), "0 '\\000'");
+        let b = Type::base("_Bool", 1, Encoding::Boolean);
+        assert_eq!(b.format_value(&[1]), "true");
+        let d = Type::base("double", 8, Encoding::Float);
+        assert_eq!(d.format_value(&1.5f64.to_le_bytes()), "1.5");
+        let f = Type::base("float", 4, Encoding::Float);
+        assert_eq!(f.format_value(&(-0.25f32).to_le_bytes()), "-0.25");
+        let p = Type::pointer_to(int);
+        assert_eq!(p.name, "int *");
+        assert_eq!(p.format_value(&0x401136u64.to_le_bytes()), "0x401136");
+    }
+
+    #[test]
+    fn format_compound_values() {
+        let int = Type::base("int", 4, Encoding::Signed);
+        let point = Type {
+            name: "struct point".to_string(),
+            size: 8,
+            kind: TypeKind::Struct(vec![
+                Member {
+                    name: "x".to_string(),
+                    offset: 0,
+                    member_type: int.clone(),
+                },
+                Member {
+                    name: "y".to_string(),
+                    offset: 4,
+                    member_type: int.clone(),
+                },
+            ]),
+        };
+        let mut bytes = 1i32.to_le_bytes().to_vec();
+        bytes.extend(&(-2i32).to_le_bytes());
+        assert_eq!(point.format_value(&bytes), "{x = 1, y = -2}");
+
+        let array = Type {
+            name: "int [2]".to_string(),
+            size: 8,
+            kind: TypeKind::Array(Box::new(int), Some(2)),
+        };
+        assert_eq!(array.format_value(&bytes), "{1, -2}");
+
+        let string = Type {
+            name: "char [8]".to_string(),
+            size: 8,
+            kind: TypeKind::Array(
+                Box::new(Type::base("char", 1, Encoding::SignedChar)),
+                Some(8),
+            ),
+        };
+        assert_eq!(string.format_value(b"hi\0junk\0"), "\"hi\"");
+
+        let color = Type {
+            name: "enum color".to_string(),
+            size: 4,
+            kind: TypeKind::Enum(vec![("RED".to_string(), 0), ("GREEN".to_string(), 1)]),
+        };
+        assert_eq!(color.format_value(&1i32.to_le_bytes()), "GREEN");
+        assert_eq!(color.format_value(&7i32.to_le_bytes()), "7");
+
+        let typedef = Type {
+            name: "colour_t".to_string(),
+            size: 4,
+            kind: TypeKind::Typedef(Box::new(color)),
+        };
+        assert_eq!(typedef.format_value(&0i32.to_le_bytes()), "RED");
+    }
+}
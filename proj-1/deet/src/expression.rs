@@ -0,0 +1,1154 @@
+//! C expressions, as `print` and breakpoint conditions take them: variables, `$register`s,
+//! integer, floating point and character constants, the arithmetic, comparison and logical
+//! operators, `*`, `&`, `.`, `->`, `[]` and casts. Expressions are evaluated against the
+//! inferior's memory, using the types from the DWARF info.
+
+use crate::dwarf_data::{Encoding, Type, TypeKind};
+use std::cmp::Ordering;
+use std::convert::TryInto;
+
+/// Words that can start a type name in a cast
+const TYPE_KEYWORDS: &[&str] = &[
+    "struct", "union", "enum", "unsigned", "signed", "int", "char", "long", "short", "float",
+    "double", "void", "_Bool", "const", "volatile",
+];
+
+/// Operators and punctuation, longest first so that `->` isn't read as `-`
+const PUNCTUATION: &[&str] = &[
+    "->", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "&",
+    "|", "^", "!", "~", "(", ")", "[", "]", ".", "=",
+];
+
+#[derive(Debug, Clone)]
+enum Token {
+    Integer(i64, Type),
+    Float(f64),
+    Identifier(String),
+    Register(String),
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+    Complement,
+    Deref,
+    AddressOf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    /// Returns the operator `punct` stands for between two operands, and its precedence (higher
+    /// binds tighter).
+    fn from_punct(punct: &str) -> Option<(BinaryOp, u8)> {
+        let op = match punct {
+            "*" => (BinaryOp::Mul, 10),
+            "/" => (BinaryOp::Div, 10),
+            "%" => (BinaryOp::Rem, 10),
+            "+" => (BinaryOp::Add, 9),
+            "-" => (BinaryOp::Sub, 9),
+            "<<" => (BinaryOp::Shl, 8),
+            ">>" => (BinaryOp::Shr, 8),
+            "<" => (BinaryOp::Lt, 7),
+            "<=" => (BinaryOp::Le, 7),
+            ">" => (BinaryOp::Gt, 7),
+            ">=" => (BinaryOp::Ge, 7),
+            "==" => (BinaryOp::Eq, 6),
+            "!=" => (BinaryOp::Ne, 6),
+            "&" => (BinaryOp::BitAnd, 5),
+            "^" => (BinaryOp::BitXor, 4),
+            "|" => (BinaryOp::BitOr, 3),
+            "&&" => (BinaryOp::And, 2),
+            "||" => (BinaryOp::Or, 1),
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn is_comparison(self) -> bool {
+        match self {
+            BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge
+            | BinaryOp::Eq
+            | BinaryOp::Ne => true,
+            _ => false,
+        }
+    }
+}
+
+/// The type named in a cast: the words of its name (e.g. `unsigned long` or `struct node`) and
+/// how many `*`s follow them.
+#[derive(Debug, Clone)]
+pub struct TypeName {
+    pub words: Vec<String>,
+    pub pointers: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Expression {
+    Integer(i64, Type),
+    Float(f64),
+    Variable(String),
+    /// `$name`, without the `$`
+    Register(String),
+    Unary(UnaryOp, Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    /// `a.b`; `a->b` is parsed as `(*a).b`
+    Member(Box<Expression>, String),
+    Index(Box<Expression>, Box<Expression>),
+    Cast(TypeName, Box<Expression>),
+    /// `lhs = rhs`, which is only allowed as a whole expression
+    Assign(Box<Expression>, Box<Expression>),
+}
+
+/// Splits `text` into tokens.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let bytes = text.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos] as char;
+        if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() {
+            let len = text[pos..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or_else(|| text.len() - pos);
+            tokens.push(parse_number(&text[pos..pos + len])?);
+            pos += len;
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let len = text[pos + 1..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or_else(|| text.len() - pos - 1)
+                + 1;
+            let word = &text[pos..pos + len];
+            if c == '$' {
+                if word.len() == 1 {
+                    return Err("Invalid register `$'".to_string());
+                }
+                tokens.push(Token::Register(word[1..].to_string()));
+            } else {
+                tokens.push(Token::Identifier(word.to_string()));
+            }
+            pos += len;
+        } else if c == '\'' {
+            let (value, len) = parse_char(&text[pos..])?;
+            tokens.push(Token::Integer(value, integer_type(1, false)));
+            pos += len;
+        } else {
+            match PUNCTUATION
+                .iter()
+                .find(|punct| text[pos..].starts_with(*punct))
+            {
+                Some(punct) => {
+                    tokens.push(Token::Punct(punct));
+                    pos += punct.len();
+                }
+                None => return Err(format!("Invalid character '{}' in expression.", c)),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses a decimal, hex (0x...), octal (0...) or floating point constant. Integers get the
+/// type C would give them: the first of `int`, `unsigned int` (except for decimal constants),
+/// `long` and `unsigned long` that can hold them, narrowed down by `u` and `l` suffixes.
+fn parse_number(text: &str) -> Result<Token, String> {
+    let invalid = || format!("Invalid number \"{}\".", text);
+    let lower = text.to_lowercase();
+    if !lower.starts_with("0x") && (lower.contains('.') || lower.contains('e')) {
+        return lower.parse().map(Token::Float).map_err(|_| invalid());
+    }
+    let digits = lower.trim_end_matches(|c| c == 'u' || c == 'l');
+    let suffix = &lower[digits.len()..];
+    let (radix, digits) = if digits.starts_with("0x") {
+        (16, &digits[2..])
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+    let value = u64::from_str_radix(digits, radix).map_err(|_| invalid())?;
+    let int_allowed = !suffix.contains('l');
+    let signed_allowed = !suffix.contains('u');
+    let unsigned_allowed = radix != 10 || suffix.contains('u');
+    let candidates = [(4, false), (4, true), (8, false), (8, true)];
+    let (size, unsigned) = candidates
+        .iter()
+        .filter(|(size, unsigned)| {
+            (*size == 8 || int_allowed)
+                && if *unsigned {
+                    unsigned_allowed
+                } else {
+                    signed_allowed
+                }
+        })
+        .find(|(size, unsigned)| match (size, unsigned) {
+            (4, false) => value <= i32::MAX as u64,
+            (4, true) => value <= u32::MAX as u64,
+            (_, false) => value <= i64::MAX as u64,
+            _ => true,
+        })
+        .copied()
+        .unwrap_or((8, true));
+    Ok(Token::Integer(value as i64, integer_type(size, unsigned)))
+}
+
+/// Parses the character constant at the start of `text`, returning its value and length.
+fn parse_char(text: &str) -> Result<(i64, usize), String> {
+    let bytes = text.as_bytes();
+    let (value, len) = match bytes.get(1) {
+        Some(b'\\') => {
+            let value = match bytes.get(2) {
+                Some(b'n') => b'\n',
+                Some(b't') => b'\t',
+                Some(b'r') => b'\r',
+                Some(b'0') => 0,
+                Some(b'\\') => b'\\',
+                Some(b'\'') => b'\'',
+                Some(b'"') => b'"',
+                _ => return Err("Unmatched single quote.".to_string()),
+            };
+            (value, 4)
+        }
+        Some(c) => (*c, 3),
+        None => return Err("Unmatched single quote.".to_string()),
+    };
+    if bytes.get(len - 1) != Some(&b'\'') {
+        return Err("Unmatched single quote.".to_string());
+    }
+    Ok((value as i64, len))
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// Says whether an identifier names a type (a typedef), so `(name)` can be told apart from a
+    /// parenthesized variable
+    is_type: &'a dyn Fn(&str) -> bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_punct(&self) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Punct(punct)) => Some(punct),
+            _ => None,
+        }
+    }
+
+    /// Consumes the next token if it is `punct`.
+    fn eat(&mut self, punct: &str) -> bool {
+        if self.peek_punct() == Some(punct) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        if self.eat(punct) {
+            Ok(())
+        } else {
+            Err(self.syntax_error())
+        }
+    }
+
+    fn syntax_error(&self) -> String {
+        let rest: Vec<String> = self.tokens[self.pos.min(self.tokens.len())..]
+            .iter()
+            .map(|token| match token {
+                Token::Integer(value, _) => format!("{}", value),
+                Token::Float(value) => format!("{}", value),
+                Token::Identifier(name) => name.clone(),
+                Token::Register(name) => format!("${}", name),
+                Token::Punct(punct) => punct.to_string(),
+            })
+            .collect();
+        format!("A syntax error in expression, near `{}'.", rest.join(" "))
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => Err(self.syntax_error()),
+        }
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<Expression, String> {
+        let mut lhs = self.unary()?;
+        while let Some((op, precedence)) = self.peek_punct().and_then(BinaryOp::from_punct) {
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = Expression::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let op = match self.peek_punct() {
+            Some("-") => Some(UnaryOp::Negate),
+            Some("!") => Some(UnaryOp::Not),
+            Some("~") => Some(UnaryOp::Complement),
+            Some("*") => Some(UnaryOp::Deref),
+            Some("&") => Some(UnaryOp::AddressOf),
+            Some("+") => {
+                self.pos += 1;
+                return self.unary();
+            }
+            Some("(") if self.type_follows() => {
+                self.pos += 1;
+                let type_name = self.type_name()?;
+                self.expect(")")?;
+                let operand = self.unary()?;
+                return Ok(Expression::Cast(type_name, Box::new(operand)));
+            }
+            _ => None,
+        };
+        match op {
+            Some(op) => {
+                self.pos += 1;
+                Ok(Expression::Unary(op, Box::new(self.unary()?)))
+            }
+            None => self.postfix(),
+        }
+    }
+
+    /// Returns true if the token after the current `(` starts a type name.
+    fn type_follows(&self) -> bool {
+        match self.tokens.get(self.pos + 1) {
+            Some(Token::Identifier(name)) => {
+                TYPE_KEYWORDS.contains(&name.as_str()) || (self.is_type)(name)
+            }
+            _ => false,
+        }
+    }
+
+    fn type_name(&mut self) -> Result<TypeName, String> {
+        let mut words = Vec::new();
+        while let Some(Token::Identifier(word)) = self.peek() {
+            let word = word.clone();
+            match word.as_str() {
+                "const" | "volatile" => (),
+                "struct" | "union" | "enum" => {
+                    self.pos += 1;
+                    let tag = self.identifier()?;
+                    words.push(format!("{} {}", word, tag));
+                    break;
+                }
+                _ if TYPE_KEYWORDS.contains(&word.as_str()) => words.push(word),
+                // A typedef name stands alone
+                _ if words.is_empty() => {
+                    self.pos += 1;
+                    words.push(word);
+                    break;
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let mut pointers = 0;
+        loop {
+            if self.eat("*") {
+                pointers += 1;
+            } else if let Some(Token::Identifier(word)) = self.peek() {
+                // e.g. `char * const`
+                if word != "const" && word != "volatile" {
+                    break;
+                }
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        if words.is_empty() {
+            return Err(self.syntax_error());
+        }
+        Ok(TypeName { words, pointers })
+    }
+
+    fn postfix(&mut self) -> Result<Expression, String> {
+        let mut expression = self.primary()?;
+        loop {
+            if self.eat("[") {
+                let index = self.binary(0)?;
+                self.expect("]")?;
+                expression = Expression::Index(Box::new(expression), Box::new(index));
+            } else if self.eat(".") {
+                expression = Expression::Member(Box::new(expression), self.identifier()?);
+            } else if self.eat("->") {
+                let target = Expression::Unary(UnaryOp::Deref, Box::new(expression));
+                expression = Expression::Member(Box::new(target), self.identifier()?);
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        let expression = match self.peek() {
+            Some(Token::Integer(value, value_type)) => {
+                Expression::Integer(*value, value_type.clone())
+            }
+            Some(Token::Float(value)) => Expression::Float(*value),
+            Some(Token::Identifier(name)) => Expression::Variable(name.clone()),
+            Some(Token::Register(name)) => Expression::Register(name.clone()),
+            Some(Token::Punct("(")) => {
+                self.pos += 1;
+                let expression = self.binary(0)?;
+                self.expect(")")?;
+                return Ok(expression);
+            }
+            _ => return Err(self.syntax_error()),
+        };
+        self.pos += 1;
+        Ok(expression)
+    }
+}
+
+/// Parses `text` as a C expression. `is_type` says whether an identifier names a typedef.
+pub fn parse(text: &str, is_type: &dyn Fn(&str) -> bool) -> Result<Expression, String> {
+    let mut parser = Parser {
+        tokens: tokenize(text)?,
+        pos: 0,
+        is_type,
+    };
+    if parser.tokens.is_empty() {
+        return Err("Argument required (expression to compute).".to_string());
+    }
+    let mut expression = parser.binary(0)?;
+    if parser.eat("=") {
+        let value = parser.binary(0)?;
+        expression = Expression::Assign(Box::new(expression), Box::new(value));
+    }
+    if parser.pos < parser.tokens.len() {
+        return Err(parser.syntax_error());
+    }
+    Ok(expression)
+}
+
+/// The result of evaluating an expression.
+#[derive(Debug, Clone)]
+pub struct Value {
+    pub value_type: Type,
+    /// The value's raw (little-endian) bytes
+    pub bytes: Vec<u8>,
+    /// Where the value lives in the inferior, if it is an object there rather than the result of
+    /// a computation
+    pub address: Option<usize>,
+}
+
+impl Value {
+    pub fn from_integer(value_type: Type, value: i64) -> Value {
+        let size = value_type.size.min(8);
+        Value {
+            value_type,
+            bytes: value.to_le_bytes()[..size].to_vec(),
+            address: None,
+        }
+    }
+
+    fn from_float(value_type: Type, value: f64) -> Value {
+        let bytes = if value_type.size == 4 {
+            (value as f32).to_le_bytes().to_vec()
+        } else {
+            value.to_le_bytes().to_vec()
+        };
+        Value {
+            value_type,
+            bytes,
+            address: None,
+        }
+    }
+
+    fn scalar(&self) -> Result<Scalar, String> {
+        let resolved = self.value_type.resolved();
+        let integer = |unsigned| {
+            Ok(Scalar::Integer {
+                value: self.value_type.value_as_i64(&self.bytes),
+                unsigned,
+                size: self.bytes.len(),
+            })
+        };
+        match &resolved.kind {
+            TypeKind::Base(Encoding::Float) => match self.bytes.len() {
+                4 => Ok(Scalar::Float(
+                    f32::from_le_bytes(self.bytes[..].try_into().unwrap()) as f64,
+                )),
+                8 => Ok(Scalar::Float(f64::from_le_bytes(
+                    self.bytes[..].try_into().unwrap(),
+                ))),
+                _ => Err(format!("Cannot handle a {}-byte float.", self.bytes.len())),
+            },
+            TypeKind::Base(Encoding::Unsigned)
+            | TypeKind::Base(Encoding::UnsignedChar)
+            | TypeKind::Base(Encoding::Boolean)
+            | TypeKind::Pointer(_) => integer(true),
+            TypeKind::Base(_) | TypeKind::Enum(_) => integer(false),
+            // Arrays and functions stand for their address
+            TypeKind::Array(..) | TypeKind::Function => match self.address {
+                Some(address) => Ok(Scalar::Integer {
+                    value: address as i64,
+                    unsigned: true,
+                    size: 8,
+                }),
+                None => Err("Attempt to take address of value not located in memory.".to_string()),
+            },
+            _ => Err("Argument to arithmetic operation not a number or boolean.".to_string()),
+        }
+    }
+
+    /// Returns true if the value is nonzero, as a condition.
+    pub fn is_true(&self) -> Result<bool, String> {
+        Ok(match self.scalar()? {
+            Scalar::Integer { value, .. } => value != 0,
+            Scalar::Float(value) => value != 0.0,
+        })
+    }
+
+    /// Returns the value as an address: a pointer or integer's value, or where an array or
+    /// function is.
+    pub fn as_address(&self) -> Result<usize, String> {
+        match self.scalar()? {
+            Scalar::Integer { value, .. } => Ok(value as usize),
+            Scalar::Float(_) => Err("Invalid cast.".to_string()),
+        }
+    }
+}
+
+/// A value reduced to a number for arithmetic.
+#[derive(Debug, Clone, Copy)]
+enum Scalar {
+    Integer {
+        value: i64,
+        unsigned: bool,
+        size: usize,
+    },
+    Float(f64),
+}
+
+impl Scalar {
+    fn as_f64(self) -> f64 {
+        match self {
+            Scalar::Integer {
+                value,
+                unsigned: true,
+                ..
+            } => value as u64 as f64,
+            Scalar::Integer { value, .. } => value as f64,
+            Scalar::Float(value) => value,
+        }
+    }
+
+    fn as_i64(self) -> i64 {
+        match self {
+            Scalar::Integer { value, .. } => value,
+            Scalar::Float(value) => value as i64,
+        }
+    }
+}
+
+/// What an expression is evaluated against: the inferior's variables, registers and memory as
+/// seen from the selected frame, and the program's types.
+pub trait Scope {
+    /// Returns the variable called `name`.
+    fn variable(&self, name: &str) -> Result<Value, String>;
+    /// Returns the register called `name` (without the `$`).
+    fn register(&self, name: &str) -> Result<Value, String>;
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String>;
+    /// Returns the type a pointer of type `pointer_type` points to, or None for `void *`.
+    fn pointer_target(&self, pointer_type: &Type) -> Option<Type>;
+    /// Returns the type called `name` (e.g. `struct node` or a typedef name).
+    fn find_type(&self, name: &str) -> Option<Type>;
+}
+
+/// Returns the C integer type of `size` bytes.
+pub fn integer_type(size: usize, unsigned: bool) -> Type {
+    let name = match size {
+        1 => "char",
+        2 => "short",
+        4 => "int",
+        _ => "long",
+    };
+    match (size, unsigned) {
+        (1, true) => Type::base("unsigned char", 1, Encoding::UnsignedChar),
+        (1, false) => Type::base(name, 1, Encoding::SignedChar),
+        (_, true) => Type::base(&format!("unsigned {}", name), size, Encoding::Unsigned),
+        (_, false) => Type::base(name, size, Encoding::Signed),
+    }
+}
+
+fn int_type() -> Type {
+    integer_type(4, false)
+}
+
+fn double_type() -> Type {
+    Type::base("double", 8, Encoding::Float)
+}
+
+/// Returns the base type a cast spells with `words` (e.g. `unsigned`, `long`, `int`), if it is
+/// one.
+fn base_type(words: &[String]) -> Option<Type> {
+    let has = |word: &str| words.iter().any(|w| w == word);
+    let unsigned = has("unsigned");
+    let base = if has("void") {
+        Type {
+            name: "void".to_string(),
+            size: 1,
+            kind: TypeKind::Void,
+        }
+    } else if has("_Bool") {
+        Type::base("_Bool", 1, Encoding::Boolean)
+    } else if has("float") {
+        Type::base("float", 4, Encoding::Float)
+    } else if has("double") {
+        double_type()
+    } else if has("char") {
+        integer_type(1, unsigned)
+    } else if has("short") {
+        integer_type(2, unsigned)
+    } else if has("long") {
+        integer_type(8, unsigned)
+    } else if has("int") || unsigned || has("signed") {
+        integer_type(4, unsigned)
+    } else {
+        return None;
+    };
+    Some(base)
+}
+
+/// Evaluates `expression` in `scope`.
+pub fn evaluate(expression: &Expression, scope: &dyn Scope) -> Result<Value, String> {
+    match expression {
+        Expression::Integer(value, value_type) => {
+            Ok(Value::from_integer(value_type.clone(), *value))
+        }
+        Expression::Float(value) => Ok(Value::from_float(double_type(), *value)),
+        Expression::Variable(name) => scope.variable(name),
+        Expression::Register(name) => scope.register(name),
+        Expression::Unary(op, operand) => {
+            let operand = evaluate(operand, scope)?;
+            unary(*op, operand, scope)
+        }
+        Expression::Binary(BinaryOp::And, lhs, rhs)
+        | Expression::Binary(BinaryOp::Or, lhs, rhs) => {
+            let is_and = match expression {
+                Expression::Binary(BinaryOp::And, ..) => true,
+                _ => false,
+            };
+            // Like C, the right operand is only evaluated if it decides the result
+            let lhs = evaluate(lhs, scope)?.is_true()?;
+            let result = if lhs == is_and {
+                evaluate(rhs, scope)?.is_true()?
+            } else {
+                lhs
+            };
+            Ok(Value::from_integer(int_type(), result as i64))
+        }
+        Expression::Binary(op, lhs, rhs) => {
+            let lhs = evaluate(lhs, scope)?;
+            let rhs = evaluate(rhs, scope)?;
+            binary(*op, lhs, rhs, scope)
+        }
+        Expression::Member(operand, name) => {
+            let operand = evaluate(operand, scope)?;
+            let member = match operand.value_type.resolved().kind {
+                TypeKind::Struct(_) | TypeKind::Union(_) => operand
+                    .value_type
+                    .member(name)
+                    .ok_or_else(|| format!("There is no member named {}.", name))?,
+                _ => {
+                    return Err(
+                        "Attempt to extract a component of a value that is not a structure."
+                            .to_string(),
+                    )
+                }
+            };
+            let start = member.offset.min(operand.bytes.len());
+            let end = (member.offset + member.member_type.size).min(operand.bytes.len());
+            Ok(Value {
+                value_type: member.member_type.clone(),
+                bytes: operand.bytes[start..end].to_vec(),
+                address: operand.address.map(|addr| addr + member.offset),
+            })
+        }
+        Expression::Index(array, index) => {
+            let array = evaluate(array, scope)?;
+            if pointed_type(&array, scope).is_none() {
+                return Err(format!(
+                    "cannot subscript something of type `{}'",
+                    array.value_type.name
+                ));
+            }
+            let index = evaluate(index, scope)?;
+            let element = binary(BinaryOp::Add, array, index, scope)?;
+            unary(UnaryOp::Deref, element, scope)
+        }
+        Expression::Cast(type_name, operand) => {
+            let operand = evaluate(operand, scope)?;
+            let mut target = match base_type(&type_name.words) {
+                Some(base) => base,
+                None => {
+                    let name = type_name.words.join(" ");
+                    scope
+                        .find_type(&name)
+                        .ok_or_else(|| format!("No symbol \"{}\" in current context.", name))?
+                }
+            };
+            for _ in 0..type_name.pointers {
+                target = Type::pointer_to(target);
+            }
+            cast(operand, target)
+        }
+        Expression::Assign(..) => Err("Assignments can only be made with `set var`.".to_string()),
+    }
+}
+
+/// Works out what the assignment `lhs = rhs` stores: returns `rhs` converted to the type of
+/// `lhs`, with the address of `lhs`. Writing it to the inferior is up to the caller.
+pub fn assignment(lhs: &Expression, rhs: &Expression, scope: &dyn Scope) -> Result<Value, String> {
+    let target = evaluate(lhs, scope)?;
+    let addr = target
+        .address
+        .ok_or_else(|| "Left operand of assignment is not an lvalue.".to_string())?;
+    let mut value = cast(evaluate(rhs, scope)?, target.value_type)?;
+    value.address = Some(addr);
+    Ok(value)
+}
+
+/// Returns the type of what `value` points to if it is a pointer or an array (which stands for a
+/// pointer to its first element). `void *` points to `void`.
+fn pointed_type(value: &Value, scope: &dyn Scope) -> Option<Type> {
+    match &value.value_type.resolved().kind {
+        TypeKind::Array(element, _) => Some((**element).clone()),
+        TypeKind::Pointer(_) => Some(
+            scope
+                .pointer_target(&value.value_type)
+                .unwrap_or_else(|| base_type(&["void".to_string()]).unwrap()),
+        ),
+        _ => None,
+    }
+}
+
+fn unary(op: UnaryOp, operand: Value, scope: &dyn Scope) -> Result<Value, String> {
+    match op {
+        UnaryOp::Deref => {
+            let target = pointed_type(&operand, scope)
+                .filter(|target| match target.resolved().kind {
+                    TypeKind::Void => false,
+                    _ => true,
+                })
+                .ok_or_else(|| "Attempt to take contents of a non-pointer value.".to_string())?;
+            let addr = operand.as_address()?;
+            let bytes = match target.resolved().kind {
+                TypeKind::Function => Vec::new(),
+                _ => scope.read_memory(addr, target.size)?,
+            };
+            Ok(Value {
+                value_type: target,
+                bytes,
+                address: Some(addr),
+            })
+        }
+        UnaryOp::AddressOf => {
+            let addr = operand.address.ok_or_else(|| {
+                "Attempt to take address of value not located in memory.".to_string()
+            })?;
+            Ok(Value::from_integer(
+                Type::pointer_to(operand.value_type),
+                addr as i64,
+            ))
+        }
+        UnaryOp::Not => Ok(Value::from_integer(int_type(), !operand.is_true()? as i64)),
+        UnaryOp::Negate => match operand.scalar()? {
+            Scalar::Float(value) => Ok(Value::from_float(double_type(), -value)),
+            Scalar::Integer {
+                value,
+                unsigned,
+                size,
+            } => Ok(Value::from_integer(
+                integer_type(size.max(4), unsigned),
+                value.wrapping_neg(),
+            )),
+        },
+        UnaryOp::Complement => match operand.scalar()? {
+            Scalar::Float(_) => Err("Argument to complement operation not an integer.".to_string()),
+            Scalar::Integer {
+                value,
+                unsigned,
+                size,
+            } => Ok(Value::from_integer(
+                integer_type(size.max(4), unsigned),
+                !value,
+            )),
+        },
+    }
+}
+
+fn binary(op: BinaryOp, lhs: Value, rhs: Value, scope: &dyn Scope) -> Result<Value, String> {
+    // Pointer arithmetic moves in units of the pointed-to type
+    let lhs_target = pointed_type(&lhs, scope);
+    let rhs_target = pointed_type(&rhs, scope);
+    let element_size = |target: &Type| match target.resolved().kind {
+        TypeKind::Void => 1,
+        _ => target.size.max(1) as i64,
+    };
+    match (op, &lhs_target, &rhs_target) {
+        (BinaryOp::Add, Some(target), None) | (BinaryOp::Sub, Some(target), None) => {
+            let offset = rhs.scalar()?.as_i64().wrapping_mul(element_size(target));
+            let base = lhs.as_address()? as i64;
+            let addr = if op == BinaryOp::Add {
+                base.wrapping_add(offset)
+            } else {
+                base.wrapping_sub(offset)
+            };
+            return Ok(Value::from_integer(Type::pointer_to(target.clone()), addr));
+        }
+        (BinaryOp::Add, None, Some(target)) => {
+            let offset = lhs.scalar()?.as_i64().wrapping_mul(element_size(target));
+            let addr = (rhs.as_address()? as i64).wrapping_add(offset);
+            return Ok(Value::from_integer(Type::pointer_to(target.clone()), addr));
+        }
+        (BinaryOp::Sub, Some(target), Some(_)) => {
+            let difference = (lhs.as_address()? as i64).wrapping_sub(rhs.as_address()? as i64);
+            return Ok(Value::from_integer(
+                integer_type(8, false),
+                difference / element_size(target),
+            ));
+        }
+        _ => (),
+    }
+
+    let lhs = lhs.scalar()?;
+    let rhs = rhs.scalar()?;
+    if let (Scalar::Integer { .. }, Scalar::Integer { .. }) = (lhs, rhs) {
+        return integer_binary(op, lhs, rhs);
+    }
+    let (l, r) = (lhs.as_f64(), rhs.as_f64());
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div => l / r,
+        BinaryOp::Lt => return Ok(Value::from_integer(int_type(), (l < r) as i64)),
+        BinaryOp::Le => return Ok(Value::from_integer(int_type(), (l <= r) as i64)),
+        BinaryOp::Gt => return Ok(Value::from_integer(int_type(), (l > r) as i64)),
+        BinaryOp::Ge => return Ok(Value::from_integer(int_type(), (l >= r) as i64)),
+        BinaryOp::Eq => return Ok(Value::from_integer(int_type(), (l == r) as i64)),
+        BinaryOp::Ne => return Ok(Value::from_integer(int_type(), (l != r) as i64)),
+        _ => return Err("Integer only operation.".to_string()),
+    };
+    Ok(Value::from_float(double_type(), result))
+}
+
+/// Applies `op` to two integers, converting them to a common type the way C does: at least
+/// `int`, as wide as the wider operand, and unsigned if the wider operand is.
+fn integer_binary(op: BinaryOp, lhs: Scalar, rhs: Scalar) -> Result<Value, String> {
+    let (l, l_unsigned, l_size) = match lhs {
+        Scalar::Integer {
+            value,
+            unsigned,
+            size,
+        } => (value, unsigned, size.max(4)),
+        Scalar::Float(_) => unreachable!(),
+    };
+    let (r, r_unsigned, r_size) = match rhs {
+        Scalar::Integer {
+            value,
+            unsigned,
+            size,
+        } => (value, unsigned, size.max(4)),
+        Scalar::Float(_) => unreachable!(),
+    };
+    let size = l_size.max(r_size);
+    let unsigned = (l_unsigned && l_size == size) || (r_unsigned && r_size == size);
+    // Reduce both operands to the common type before operating on them
+    let truncate = |value: i64| -> i64 {
+        match (size, unsigned) {
+            (4, true) => value as u32 as i64,
+            (4, false) => value as i32 as i64,
+            _ => value,
+        }
+    };
+    let (l, r) = (truncate(l), truncate(r));
+    if op.is_comparison() {
+        let ordering = if unsigned {
+            (l as u64).cmp(&(r as u64))
+        } else {
+            l.cmp(&r)
+        };
+        let result = match op {
+            BinaryOp::Lt => ordering == Ordering::Less,
+            BinaryOp::Le => ordering != Ordering::Greater,
+            BinaryOp::Gt => ordering == Ordering::Greater,
+            BinaryOp::Ge => ordering != Ordering::Less,
+            BinaryOp::Eq => ordering == Ordering::Equal,
+            _ => ordering != Ordering::Equal,
+        };
+        return Ok(Value::from_integer(int_type(), result as i64));
+    }
+    let result = match op {
+        BinaryOp::Add => l.wrapping_add(r),
+        BinaryOp::Sub => l.wrapping_sub(r),
+        BinaryOp::Mul => l.wrapping_mul(r),
+        BinaryOp::Div | BinaryOp::Rem if r == 0 => return Err("Division by zero".to_string()),
+        BinaryOp::Div if unsigned => ((l as u64) / (r as u64)) as i64,
+        BinaryOp::Div => l.wrapping_div(r),
+        BinaryOp::Rem if unsigned => ((l as u64) % (r as u64)) as i64,
+        BinaryOp::Rem => l.wrapping_rem(r),
+        BinaryOp::BitAnd => l & r,
+        BinaryOp::BitOr => l | r,
+        BinaryOp::BitXor => l ^ r,
+        // The result of a shift has the type of its left operand
+        BinaryOp::Shl => {
+            return Ok(Value::from_integer(
+                integer_type(l_size, l_unsigned),
+                l.wrapping_shl(r as u32),
+            ))
+        }
+        BinaryOp::Shr if l_unsigned => {
+            let l = if l_size == 4 {
+                l as u32 as u64
+            } else {
+                l as u64
+            };
+            return Ok(Value::from_integer(
+                integer_type(l_size, l_unsigned),
+                l.wrapping_shr(r as u32) as i64,
+            ));
+        }
+        BinaryOp::Shr => {
+            return Ok(Value::from_integer(
+                integer_type(l_size, l_unsigned),
+                l.wrapping_shr(r as u32),
+            ))
+        }
+        _ => unreachable!(),
+    };
+    Ok(Value::from_integer(integer_type(size, unsigned), result))
+}
+
+/// Converts `value` to `target`, as a C cast does.
+fn cast(value: Value, target: Type) -> Result<Value, String> {
+    match target.resolved().kind {
+        TypeKind::Void => Ok(Value {
+            value_type: target,
+            bytes: Vec::new(),
+            address: None,
+        }),
+        TypeKind::Base(Encoding::Float) => {
+            let number = value.scalar()?.as_f64();
+            Ok(Value::from_float(target, number))
+        }
+        TypeKind::Base(_) | TypeKind::Enum(_) | TypeKind::Pointer(_) => {
+            let number = value.scalar()?.as_i64();
+            Ok(Value::from_integer(target, number))
+        }
+        // A struct can only be "cast" to its own type
+        _ if target.name == value.value_type.name => Ok(value),
+        _ => Err("Invalid cast.".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dwarf_data::Pointee;
+
+    /// A scope with an int `x` (5) at 0x1000 and an int pointer `p` to it.
+    struct TestScope {
+        memory: Vec<u8>,
+    }
+
+    const BASE: usize = 0x1000;
+
+    impl TestScope {
+        fn new() -> TestScope {
+            TestScope {
+                memory: 5i32.to_le_bytes().to_vec(),
+            }
+        }
+    }
+
+    impl Scope for TestScope {
+        fn variable(&self, name: &str) -> Result<Value, String> {
+            match name {
+                "x" => Ok(Value {
+                    value_type: int_type(),
+                    bytes: self.read_memory(BASE, 4)?,
+                    address: Some(BASE),
+                }),
+                "p" => Ok(Value::from_integer(
+                    Type::pointer_to(int_type()),
+                    BASE as i64,
+                )),
+                _ => Err(format!("No symbol \"{}\" in current context.", name)),
+            }
+        }
+
+        fn register(&self, name: &str) -> Result<Value, String> {
+            match name {
+                "rax" => Ok(Value::from_integer(integer_type(8, false), 0x2a)),
+                _ => Err(format!("Invalid register `{}'", name)),
+            }
+        }
+
+        fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String> {
+            addr.checked_sub(BASE)
+                .and_then(|start| self.memory.get(start..start + len))
+                .map(|bytes| bytes.to_vec())
+                .ok_or_else(|| format!("Cannot access memory at address {:#x}", addr))
+        }
+
+        fn pointer_target(&self, pointer_type: &Type) -> Option<Type> {
+            match &pointer_type.resolved().kind {
+                TypeKind::Pointer(Pointee::Type(target)) => Some((**target).clone()),
+                _ => None,
+            }
+        }
+
+        fn find_type(&self, _name: &str) -> Option<Type> {
+            None
+        }
+    }
+
+    fn describe(token: &Token) -> String {
+        match token {
+            Token::Integer(value, value_type) => format!("{} ({})", value, value_type.name),
+            Token::Float(value) => format!("{}", value),
+            Token::Identifier(name) => name.clone(),
+            Token::Register(name) => format!("${}", name),
+            Token::Punct(punct) => punct.to_string(),
+        }
+    }
+
+    fn tokens(text: &str) -> Vec<String> {
+        tokenize(text).unwrap().iter().map(describe).collect()
+    }
+
+    fn eval(text: &str) -> Result<String, String> {
+        let value = evaluate(&parse(text, &|_| false)?, &TestScope::new())?;
+        Ok(value.value_type.format_value(&value.bytes))
+    }
+
+    #[test]
+    fn tokenize_takes_longest_operator() {
+        assert_eq!(
+            tokens("a->b<=$rip<<'x'"),
+            vec!["a", "->", "b", "<=", "$rip", "<<", "120 (char)"]
+        );
+        assert_eq!(tokens("x!=-1"), vec!["x", "!=", "-", "1 (int)"]);
+        assert!(tokenize("1 # 2").is_err());
+        assert!(tokenize("$").is_err());
+        assert!(tokenize("'ab'").is_err());
+    }
+
+    #[test]
+    fn parse_number_picks_c_type() {
+        let number = |text| describe(&parse_number(text).unwrap());
+        assert_eq!(number("42"), "42 (int)");
+        assert_eq!(number("010"), "8 (int)");
+        assert_eq!(number("0xffffffff"), "4294967295 (unsigned int)");
+        assert_eq!(number("4294967295"), "4294967295 (long)");
+        assert_eq!(number("10u"), "10 (unsigned int)");
+        assert_eq!(number("10l"), "10 (long)");
+        assert_eq!(number("10ul"), "10 (unsigned long)");
+        assert_eq!(number("0xffffffffffffffff"), "-1 (unsigned long)");
+        assert_eq!(number("1.5"), "1.5");
+        assert!(parse_number("09").is_err());
+        assert!(parse_number("1x").is_err());
+    }
+
+    #[test]
+    fn binary_operator_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok("7".to_string()));
+        assert_eq!(eval("(1 + 2) * 3"), Ok("9".to_string()));
+        assert_eq!(eval("7 - 2 - 1"), Ok("4".to_string()));
+        assert_eq!(eval("10 / 3 % 2"), Ok("1".to_string()));
+        assert_eq!(eval("1 << 2 + 1"), Ok("8".to_string()));
+        assert_eq!(eval("1 | 2 ^ 3 & 1"), Ok("3".to_string()));
+        assert_eq!(eval("2 < 3 == 1"), Ok("1".to_string()));
+        assert_eq!(eval("1 || 0 && 0"), Ok("1".to_string()));
+        assert_eq!(eval("-2 * 3"), Ok("-6".to_string()));
+        assert!(eval("1 +").is_err());
+        assert!(eval("(1").is_err());
+    }
+
+    #[test]
+    fn evaluate_against_scope() {
+        assert_eq!(eval("x * 2"), Ok("10".to_string()));
+        assert_eq!(eval("*p + x"), Ok("10".to_string()));
+        assert_eq!(eval("p[0]"), Ok("5".to_string()));
+        assert_eq!(eval("&x"), Ok("0x1000".to_string()));
+        assert_eq!(eval("$rax - 2"), Ok("40".to_string()));
+        assert_eq!(eval("(char)x"), Ok("5 '\\005'".to_string()));
+        assert!(eval("*(p + 1)").is_err());
+        assert!(eval("y").is_err());
+    }
+
+    fn assign(text: &str) -> Result<Value, String> {
+        match parse(text, &|_| false)? {
+            Expression::Assign(lhs, rhs) => assignment(&lhs, &rhs, &TestScope::new()),
+            _ => Err(format!("{} is not an assignment", text)),
+        }
+    }
+
+    #[test]
+    fn assignment_converts_to_lvalue_type() {
+        let value = assign("x = 7").unwrap();
+        assert_eq!(value.address, Some(BASE));
+        assert_eq!(value.bytes, 7i32.to_le_bytes().to_vec());
+        let value = assign("*p = 2.9").unwrap();
+        assert_eq!(value.address, Some(BASE));
+        assert_eq!(value.bytes, 2i32.to_le_bytes().to_vec());
+        let value = assign("*(char *)p = 'a'").unwrap();
+        assert_eq!(value.bytes, vec![b'a']);
+    }
+
+    #[test]
+    fn assignment_needs_an_lvalue() {
+        assert_eq!(
+            assign("3 = 4").unwrap_err(),
+            "Left operand of assignment is not an lvalue."
+        );
+        assert!(assign("x + 1 = 4").is_err());
+        assert!(assign("x == 4").is_err());
+        assert!(parse("x = 1 = 2", &|_| false).is_err());
+    }
+}
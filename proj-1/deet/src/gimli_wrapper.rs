@@ -10,13 +10,22 @@ use gimli::{UnitOffset, UnitSectionOffset};
 use object::Object;
 use std::borrow;
 //use std::io::{BufWriter, Write};
-use crate::dwarf_data::{File, Function, Line, Location, Type, Variable};
+use crate::dwarf_data::{
+    CallSite, DwarfExpression, Encoding, File, Function, Line, Location, Member, Pointee, Type,
+    TypeId, TypeKind, Variable,
+};
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Write;
 use std::{io, path};
 
-pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<Vec<File>, Error> {
+/// Loads the compilation units of `object`. Their types are appended to `types`, which variables'
+/// types refer to by index.
+pub fn load_file(
+    object: &object::File,
+    endian: gimli::RunTimeEndian,
+    types: &mut Vec<Type>,
+) -> Result<Vec<File>, Error> {
     // Load a section and return as `Cow<[u8]>`.
     let load_section = |id: gimli::SectionId| -> Result<borrow::Cow<[u8]>, gimli::Error> {
         Ok(object
@@ -39,8 +48,21 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
     // Create `EndianSlice`s for all of the sections.
     let dwarf = dwarf_cow.borrow(&borrow_section);
 
-    // Define a mapping from type offsets to type structs
-    let mut offset_to_type: HashMap<usize, Type> = HashMap::new();
+    // Collect every type first, since variables may refer to types that are declared after them
+    // (or, with DW_FORM_ref_addr, in another unit). Types are keyed by their offset in
+    // .debug_info, which is what DW_AT_type references resolve to.
+    let mut raw_types: HashMap<usize, RawType> = HashMap::new();
+    let mut iter = dwarf.units();
+    while let Some(header) = iter.next()? {
+        let unit = dwarf.unit(header)?;
+        let mut entries = unit.entries();
+        while let Some((_, entry)) = entries.next_dfs()? {
+            if let Some(raw_type) = RawType::parse(entry, &unit, &dwarf)? {
+                raw_types.insert(section_offset(entry.offset(), &unit), raw_type);
+            }
+        }
+    }
+    let type_ids = build_types(&raw_types, types);
 
     let mut compilation_units: Vec<File> = Vec::new();
 
@@ -54,7 +76,6 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
         let mut entries = unit.entries();
         while let Some((delta_depth, entry)) = entries.next_dfs()? {
             depth += delta_depth;
-            // Update the offset_to_type mapping for types
             // Update the variable list for formal params/variables
             match entry.tag() {
                 gimli::DW_TAG_compile_unit => {
@@ -67,40 +88,25 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                     } else {
                         "<unknown>".to_string()
                     };
+                    // A relative name is relative to the compilation directory. Make it absolute so
+                    // that it matches the paths in the line table.
+                    let name = match unit.comp_dir {
+                        Some(ref comp_dir) if !name.starts_with('/') && name != "<unknown>" => {
+                            path::Path::new(comp_dir.to_string_lossy().as_ref())
+                                .join(&name)
+                                .to_string_lossy()
+                                .into_owned()
+                        }
+                        _ => name,
+                    };
                     compilation_units.push(File {
                         name,
                         global_variables: Vec::new(),
                         functions: Vec::new(),
                         lines: Vec::new(),
+                        prologue_ends: Vec::new(),
                     });
                 }
-                gimli::DW_TAG_base_type => {
-                    let name = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_name) {
-                        if let Ok(DebugValue::Str(name)) = get_attr_value(&attr, &unit, &dwarf) {
-                            name
-                        } else {
-                            "<unknown>".to_string()
-                        }
-                    } else {
-                        "<unknown>".to_string()
-                    };
-                    let byte_size = if let Ok(Some(attr)) = entry.attr(gimli::DW_AT_byte_size) {
-                        if let Ok(DebugValue::Uint(byte_size)) =
-                            get_attr_value(&attr, &unit, &dwarf)
-                        {
-                            byte_size
-                        } else {
-                            // TODO: report error?
-                            0
-                        }
-                    } else {
-                        // TODO: report error?
-                        0
-                    };
-                    let type_offset = entry.offset().0;
-                    offset_to_type
-                        .insert(type_offset, Type::new(name, byte_size.try_into().unwrap()));
-                }
                 gimli::DW_TAG_subprogram => {
                     let mut func: Function = Default::default();
                     let mut attrs = entry.attrs();
@@ -129,11 +135,53 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                     func.line_number = line_number.try_into().unwrap();
                                 }
                             }
+                            gimli::DW_AT_frame_base => {
+                                func.frame_base = get_expression(&attr, &unit);
+                            }
                             _ => {}
                         }
                     }
                     compilation_units.last_mut().unwrap().functions.push(func);
                 }
+                gimli::DW_TAG_call_site | gimli::DW_TAG_GNU_call_site => {
+                    // DWARF 5 gives the return address; the GNU extension uses low_pc for it
+                    let return_address = [gimli::DW_AT_call_return_pc, gimli::DW_AT_low_pc]
+                        .iter()
+                        .filter_map(|name| entry.attr(*name).ok()?)
+                        .find_map(|attr| match get_attr_value(&attr, &unit, &dwarf) {
+                            Ok(DebugValue::Uint(address)) => Some(address as usize),
+                            _ => None,
+                        });
+                    let func = compilation_units
+                        .last_mut()
+                        .and_then(|file| file.functions.last_mut());
+                    if let (Some(return_address), Some(func)) = (return_address, func) {
+                        func.call_sites.push(CallSite {
+                            return_address,
+                            parameters: Vec::new(),
+                        });
+                    }
+                }
+                gimli::DW_TAG_call_site_parameter | gimli::DW_TAG_GNU_call_site_parameter => {
+                    let register = entry
+                        .attr(gimli::DW_AT_location)?
+                        .and_then(|attr| get_expression(&attr, &unit))
+                        .and_then(|expression| single_register(&expression));
+                    let value = match entry.attr(gimli::DW_AT_call_value)? {
+                        Some(attr) => Some(attr),
+                        None => entry.attr(gimli::DW_AT_GNU_call_site_value)?,
+                    }
+                    .and_then(|attr| get_expression(&attr, &unit));
+                    let call_site = compilation_units
+                        .last_mut()
+                        .and_then(|file| file.functions.last_mut())
+                        .and_then(|func| func.call_sites.last_mut());
+                    if let (Some(register), Some(value), Some(call_site)) =
+                        (register, value, call_site)
+                    {
+                        call_site.parameters.push((register, value));
+                    }
+                }
                 gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
                     let mut name = String::new();
                     let mut entity_type: Option<Type> = None;
@@ -151,16 +199,21 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             }
                             gimli::DW_AT_type => {
                                 if let Ok(DebugValue::Size(offset)) = val {
-                                    if let Some(dtype) = offset_to_type.get(&offset).clone() {
-                                        entity_type = Some(dtype.clone());
+                                    if let Some(id) = type_ids.get(&offset) {
+                                        entity_type = Some(types[*id].clone());
                                     }
                                 }
                             }
                             gimli::DW_AT_location => {
-                                if let Some(loc) = get_location(&attr, &unit) {
+                                if let Some(loc) = get_location(&attr, &unit, &dwarf)? {
                                     location = Some(loc);
                                 }
                             }
+                            gimli::DW_AT_const_value => {
+                                if let Some(bytes) = get_const_value(&attr) {
+                                    location = Some(Location::Constant(bytes));
+                                }
+                            }
                             gimli::DW_AT_decl_line => {
                                 if let Ok(DebugValue::Uint(num)) = val {
                                     line_number = num;
@@ -169,12 +222,23 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                             _ => {}
                         }
                     }
+                    // A local the compiler dropped entirely has no location at all, but the user
+                    // should still be told it exists. (Unnamed parameters belong to function
+                    // types, not functions.)
+                    if location.is_none()
+                        && depth > 1
+                        && !name.is_empty()
+                        && entry.attr(gimli::DW_AT_declaration)?.is_none()
+                    {
+                        location = Some(Location::OptimizedOut);
+                    }
                     if entity_type.is_some() && location.is_some() {
                         let var = Variable {
                             name,
                             entity_type: entity_type.unwrap(),
                             location: location.unwrap(),
                             line_number: line_number.try_into().unwrap(),
+                            is_parameter: entry.tag() == gimli::DW_TAG_formal_parameter,
                         };
                         if depth == 1 {
                             compilation_units
@@ -219,6 +283,11 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                                 .as_ref(),
                         );
                     }
+                    if path.is_relative() {
+                        if let Some(ref comp_dir) = unit.comp_dir {
+                            path = path::Path::new(comp_dir.to_string_lossy().as_ref()).join(path);
+                        }
+                    }
 
                     // Get the File
                     let file = compilation_units
@@ -230,6 +299,9 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
                     let line = row.line().unwrap_or(0);
 
                     if let Some(file) = file {
+                        if row.prologue_end() {
+                            file.prologue_ends.push(row.address().try_into().unwrap());
+                        }
                         file.lines.push(Line {
                             file: file.name.clone(),
                             number: line.try_into().unwrap(),
@@ -243,6 +315,400 @@ pub fn load_file(object: &object::File, endian: gimli::RunTimeEndian) -> Result<
     Ok(compilation_units)
 }
 
+/// Types nest at most this deep; only a malformed file could have deeper (i.e. circular) types.
+const MAX_TYPE_DEPTH: usize = 64;
+
+/// A type DIE, as read from the file, before the types it refers to are resolved.
+struct RawType {
+    tag: gimli::DwTag,
+    name: Option<String>,
+    byte_size: Option<usize>,
+    encoding: Option<gimli::DwAte>,
+    /// The DW_AT_type: the pointee, element, underlying or return type
+    target: Option<usize>,
+    /// Struct and union members: name, offset and type
+    members: Vec<(String, usize, Option<usize>)>,
+    enumerators: Vec<(String, i64)>,
+    /// The length of each dimension of an array, if known
+    dimensions: Vec<Option<usize>>,
+    /// The parameter types of a function type
+    parameters: Vec<Option<usize>>,
+}
+
+/// Returns the offset in .debug_info of the DIE at `offset` in `unit`.
+fn section_offset<R: Reader>(offset: UnitOffset, unit: &gimli::Unit<R>) -> usize {
+    match offset.to_unit_section_offset(unit) {
+        UnitSectionOffset::DebugInfoOffset(offset) => offset.0,
+        UnitSectionOffset::DebugTypesOffset(offset) => offset.0,
+    }
+}
+
+/// Returns the .debug_info offset of the DIE that attribute `name` of `entry` refers to.
+fn attr_reference<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+    unit: &gimli::Unit<R>,
+) -> Option<usize> {
+    match entry.attr_value(name).ok()?? {
+        gimli::AttributeValue::UnitRef(offset) => Some(section_offset(offset, unit)),
+        gimli::AttributeValue::DebugInfoRef(offset) => Some(offset.0),
+        _ => None,
+    }
+}
+
+fn attr_string<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Option<String> {
+    match get_attr_value(&entry.attr(name).ok()??, unit, dwarf) {
+        Ok(DebugValue::Str(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn attr_udata<R: Reader>(
+    entry: &gimli::DebuggingInformationEntry<R>,
+    name: gimli::DwAt,
+) -> Option<u64> {
+    entry.attr(name).ok()??.udata_value()
+}
+
+impl RawType {
+    /// Reads `entry` if it is a type, along with the children (members, enumerators, array
+    /// dimensions or parameters) that describe it.
+    fn parse<R: Reader>(
+        entry: &gimli::DebuggingInformationEntry<R>,
+        unit: &gimli::Unit<R>,
+        dwarf: &gimli::Dwarf<R>,
+    ) -> Result<Option<RawType>, Error> {
+        match entry.tag() {
+            gimli::DW_TAG_base_type
+            | gimli::DW_TAG_pointer_type
+            | gimli::DW_TAG_structure_type
+            | gimli::DW_TAG_union_type
+            | gimli::DW_TAG_enumeration_type
+            | gimli::DW_TAG_array_type
+            | gimli::DW_TAG_typedef
+            | gimli::DW_TAG_const_type
+            | gimli::DW_TAG_volatile_type
+            | gimli::DW_TAG_restrict_type
+            | gimli::DW_TAG_atomic_type
+            | gimli::DW_TAG_subroutine_type => (),
+            _ => return Ok(None),
+        }
+        let mut raw_type = RawType {
+            tag: entry.tag(),
+            name: attr_string(entry, gimli::DW_AT_name, unit, dwarf),
+            byte_size: attr_udata(entry, gimli::DW_AT_byte_size).map(|size| size as usize),
+            encoding: match entry.attr_value(gimli::DW_AT_encoding)? {
+                Some(gimli::AttributeValue::Encoding(encoding)) => Some(encoding),
+                _ => None,
+            },
+            target: attr_reference(entry, gimli::DW_AT_type, unit),
+            members: Vec::new(),
+            enumerators: Vec::new(),
+            dimensions: Vec::new(),
+            parameters: Vec::new(),
+        };
+        if !entry.has_children() {
+            return Ok(Some(raw_type));
+        }
+        let mut tree = unit.entries_tree(Some(entry.offset()))?;
+        let mut children = tree.root()?.children();
+        while let Some(child) = children.next()? {
+            let child = child.entry();
+            match child.tag() {
+                gimli::DW_TAG_member => raw_type.members.push((
+                    attr_string(child, gimli::DW_AT_name, unit, dwarf).unwrap_or_default(),
+                    attr_udata(child, gimli::DW_AT_data_member_location).unwrap_or(0) as usize,
+                    attr_reference(child, gimli::DW_AT_type, unit),
+                )),
+                gimli::DW_TAG_enumerator => {
+                    let value = match child.attr_value(gimli::DW_AT_const_value)? {
+                        Some(gimli::AttributeValue::Sdata(value)) => value,
+                        _ => attr_udata(child, gimli::DW_AT_const_value).unwrap_or(0) as i64,
+                    };
+                    if let Some(name) = attr_string(child, gimli::DW_AT_name, unit, dwarf) {
+                        raw_type.enumerators.push((name, value));
+                    }
+                }
+                gimli::DW_TAG_subrange_type => {
+                    let upper_bound = attr_udata(child, gimli::DW_AT_upper_bound);
+                    let count = attr_udata(child, gimli::DW_AT_count);
+                    raw_type.dimensions.push(
+                        count
+                            .or_else(|| upper_bound.map(|bound| bound + 1))
+                            .map(|count| count as usize),
+                    );
+                }
+                gimli::DW_TAG_formal_parameter => {
+                    raw_type
+                        .parameters
+                        .push(attr_reference(child, gimli::DW_AT_type, unit))
+                }
+                _ => (),
+            }
+        }
+        Ok(Some(raw_type))
+    }
+}
+
+/// Builds a Type for each of `raw_types`, appending them to `types`. Returns the TypeId of each
+/// raw type's offset.
+fn build_types(
+    raw_types: &HashMap<usize, RawType>,
+    types: &mut Vec<Type>,
+) -> HashMap<usize, TypeId> {
+    let mut offsets: Vec<usize> = raw_types.keys().copied().collect();
+    offsets.sort();
+    let ids: HashMap<usize, TypeId> = offsets
+        .iter()
+        .enumerate()
+        .map(|(index, offset)| (*offset, types.len() + index))
+        .collect();
+    let builder = TypeBuilder {
+        raw_types,
+        ids: &ids,
+    };
+    let mut built = HashMap::new();
+    for offset in offsets {
+        types.push(builder.build(Some(offset), 0, &mut built));
+    }
+    ids
+}
+
+/// Turns raw types into Types. Pointers only refer to their targets by TypeId, so building a
+/// type never goes round in circles.
+struct TypeBuilder<'a> {
+    raw_types: &'a HashMap<usize, RawType>,
+    ids: &'a HashMap<usize, TypeId>,
+}
+
+/// Formats a pointer to a type called `name`, the way C writes it (`int *`, `char **`).
+fn pointer_to(name: &str) -> String {
+    if name.ends_with('*') {
+        format!("{}*", name)
+    } else {
+        format!("{} *", name)
+    }
+}
+
+/// Formats `name` with a `const` or `volatile` qualifier; a qualified pointer has the qualifier
+/// after the `*`.
+fn qualified(qualifier: &str, name: &str) -> String {
+    if name.ends_with('*') {
+        format!("{} {}", name, qualifier)
+    } else {
+        format!("{} {}", qualifier, name)
+    }
+}
+
+/// Formats array dimensions, e.g. `[2][3]`.
+fn dimensions(dimensions: &[Option<usize>]) -> String {
+    dimensions
+        .iter()
+        .map(|dimension| match dimension {
+            Some(count) => format!("[{}]", count),
+            None => "[]".to_string(),
+        })
+        .collect()
+}
+
+impl<'a> TypeBuilder<'a> {
+    /// Returns the C name of the type at `offset` (None meaning void).
+    fn name(&self, offset: Option<usize>, depth: usize) -> String {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => return "void".to_string(),
+        };
+        let raw_type = match self.raw_types.get(&offset) {
+            Some(raw_type) if depth < MAX_TYPE_DEPTH => raw_type,
+            _ => return "<unknown type>".to_string(),
+        };
+        let tag_name = |keyword: &str| {
+            format!(
+                "{} {}",
+                keyword,
+                raw_type.name.as_deref().unwrap_or("{...}")
+            )
+        };
+        match raw_type.tag {
+            gimli::DW_TAG_pointer_type => {
+                match raw_type
+                    .target
+                    .and_then(|target| self.raw_types.get(&target))
+                {
+                    // Pointers to functions and arrays have the * in the middle
+                    Some(target) if target.tag == gimli::DW_TAG_subroutine_type => format!(
+                        "{} (*)({})",
+                        self.name(target.target, depth + 1),
+                        self.parameters(target, depth)
+                    ),
+                    Some(target) if target.tag == gimli::DW_TAG_array_type => format!(
+                        "{} (*){}",
+                        self.name(target.target, depth + 1),
+                        dimensions(&target.dimensions)
+                    ),
+                    _ => pointer_to(&self.name(raw_type.target, depth + 1)),
+                }
+            }
+            gimli::DW_TAG_structure_type => tag_name("struct"),
+            gimli::DW_TAG_union_type => tag_name("union"),
+            gimli::DW_TAG_enumeration_type => tag_name("enum"),
+            gimli::DW_TAG_array_type => format!(
+                "{} {}",
+                self.name(raw_type.target, depth + 1),
+                dimensions(&raw_type.dimensions)
+            ),
+            gimli::DW_TAG_const_type => qualified("const", &self.name(raw_type.target, depth + 1)),
+            gimli::DW_TAG_volatile_type => {
+                qualified("volatile", &self.name(raw_type.target, depth + 1))
+            }
+            gimli::DW_TAG_restrict_type | gimli::DW_TAG_atomic_type => {
+                self.name(raw_type.target, depth + 1)
+            }
+            gimli::DW_TAG_subroutine_type => format!(
+                "{} ({})",
+                self.name(raw_type.target, depth + 1),
+                self.parameters(raw_type, depth)
+            ),
+            _ => raw_type
+                .name
+                .clone()
+                .unwrap_or_else(|| "<unknown type>".to_string()),
+        }
+    }
+
+    /// Formats the parameter list of a function type.
+    fn parameters(&self, function: &RawType, depth: usize) -> String {
+        if function.parameters.is_empty() {
+            return "void".to_string();
+        }
+        let names: Vec<String> = function
+            .parameters
+            .iter()
+            .map(|parameter| self.name(*parameter, depth + 1))
+            .collect();
+        names.join(", ")
+    }
+
+    /// Builds the type at `offset` (None meaning void). Types already built are kept in `built`.
+    fn build(&self, offset: Option<usize>, depth: usize, built: &mut HashMap<usize, Type>) -> Type {
+        let offset = match offset {
+            Some(offset) => offset,
+            None => {
+                return Type {
+                    name: "void".to_string(),
+                    size: 1,
+                    kind: TypeKind::Void,
+                }
+            }
+        };
+        if let Some(built_type) = built.get(&offset) {
+            return built_type.clone();
+        }
+        let raw_type = match self.raw_types.get(&offset) {
+            Some(raw_type) if depth < MAX_TYPE_DEPTH => raw_type,
+            _ => {
+                return Type {
+                    name: "<unknown type>".to_string(),
+                    size: 0,
+                    kind: TypeKind::Void,
+                }
+            }
+        };
+        let name = self.name(Some(offset), depth);
+        let size = raw_type.byte_size.unwrap_or(0);
+        let new_type = match raw_type.tag {
+            gimli::DW_TAG_base_type => {
+                let encoding = match raw_type.encoding {
+                    Some(gimli::DW_ATE_boolean) => Encoding::Boolean,
+                    Some(gimli::DW_ATE_float) => Encoding::Float,
+                    Some(gimli::DW_ATE_signed_char) => Encoding::SignedChar,
+                    Some(gimli::DW_ATE_unsigned_char) => Encoding::UnsignedChar,
+                    Some(gimli::DW_ATE_unsigned) | Some(gimli::DW_ATE_UTF) => Encoding::Unsigned,
+                    _ => Encoding::Signed,
+                };
+                Type::base(&name, size, encoding)
+            }
+            gimli::DW_TAG_pointer_type => Type {
+                name,
+                size: raw_type.byte_size.unwrap_or(8),
+                kind: TypeKind::Pointer(
+                    match raw_type
+                        .target
+                        .and_then(|target| self.ids.get(&target).copied())
+                    {
+                        Some(id) => Pointee::Id(id),
+                        None => Pointee::Void,
+                    },
+                ),
+            },
+            gimli::DW_TAG_structure_type | gimli::DW_TAG_union_type => {
+                let members = raw_type
+                    .members
+                    .iter()
+                    .map(|(member_name, member_offset, member_type)| Member {
+                        name: member_name.clone(),
+                        offset: *member_offset,
+                        member_type: self.build(*member_type, depth + 1, built),
+                    })
+                    .collect();
+                let kind = if raw_type.tag == gimli::DW_TAG_structure_type {
+                    TypeKind::Struct(members)
+                } else {
+                    TypeKind::Union(members)
+                };
+                Type { name, size, kind }
+            }
+            gimli::DW_TAG_enumeration_type => Type {
+                name,
+                size,
+                kind: TypeKind::Enum(raw_type.enumerators.clone()),
+            },
+            gimli::DW_TAG_array_type => {
+                // int a[2][3] is an array of 2 arrays of 3 ints
+                let element_name = self.name(raw_type.target, depth + 1);
+                let mut array = self.build(raw_type.target, depth + 1, built);
+                for (index, count) in raw_type.dimensions.iter().enumerate().rev() {
+                    array = Type {
+                        name: format!(
+                            "{} {}",
+                            element_name,
+                            dimensions(&raw_type.dimensions[index..])
+                        ),
+                        size: array.size * count.unwrap_or(0),
+                        kind: TypeKind::Array(Box::new(array), *count),
+                    };
+                }
+                array
+            }
+            gimli::DW_TAG_typedef | gimli::DW_TAG_const_type | gimli::DW_TAG_volatile_type => {
+                let target = self.build(raw_type.target, depth + 1, built);
+                let size = target.size;
+                let target = Box::new(target);
+                let kind = match raw_type.tag {
+                    gimli::DW_TAG_typedef => TypeKind::Typedef(target),
+                    gimli::DW_TAG_const_type => TypeKind::Const(target),
+                    _ => TypeKind::Volatile(target),
+                };
+                Type { name, size, kind }
+            }
+            gimli::DW_TAG_subroutine_type => Type {
+                name,
+                size: 1,
+                kind: TypeKind::Function,
+            },
+            // restrict and _Atomic don't change how a value is read
+            _ => self.build(raw_type.target, depth + 1, built),
+        };
+        built.insert(offset, new_type.clone());
+        new_type
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum DebugValue {
     Str(String),
@@ -291,25 +757,83 @@ impl<'input, Endian> Reader for gimli::EndianSlice<'input, Endian> where
 
 trait Reader: gimli::Reader<Offset = usize> + Send + Sync {}
 
-fn get_location<R: Reader>(attr: &gimli::Attribute<R>, unit: &gimli::Unit<R>) -> Option<Location> {
-    if let gimli::AttributeValue::Exprloc(ref data) = attr.value() {
-        let encoding = unit.encoding();
-        let mut pc = data.0.clone();
-        if pc.len() > 0 {
-            if let Ok(op) = gimli::Operation::parse(&mut pc, encoding) {
-                match op {
-                    gimli::Operation::FrameOffset { offset } => {
-                        return Some(Location::FramePointerOffset(offset.try_into().unwrap()));
-                    }
-                    gimli::Operation::Address { address } => {
-                        return Some(Location::Address(address.try_into().unwrap()));
-                    }
-                    _ => {}
-                }
-            }
+/// Returns where the variable with the DW_AT_location `attr` lives. Single operations putting it
+/// at a fixed address or at an offset from the frame base are recognised as such; anything else
+/// is kept as an expression to evaluate once the process is running.
+fn get_location<R: Reader>(
+    attr: &gimli::Attribute<R>,
+    unit: &gimli::Unit<R>,
+    dwarf: &gimli::Dwarf<R>,
+) -> Result<Option<Location>, Error> {
+    if let Some(mut entries) = dwarf.attr_locations(unit, attr.value())? {
+        let mut list = Vec::new();
+        while let Some(entry) = entries.next()? {
+            let bytes = entry.data.0.to_slice()?.into_owned();
+            list.push((
+                entry.range.begin as usize,
+                entry.range.end as usize,
+                DwarfExpression {
+                    bytes,
+                    encoding: unit.encoding(),
+                },
+            ));
+        }
+        return Ok(Some(Location::List(list)));
+    }
+    let expression = match get_expression(attr, unit) {
+        Some(expression) => expression,
+        None => return Ok(None),
+    };
+    if expression.bytes.is_empty() {
+        return Ok(Some(Location::OptimizedOut));
+    }
+    let mut pc = gimli::EndianSlice::new(&expression.bytes, gimli::LittleEndian);
+    let location = match gimli::Operation::parse(&mut pc, unit.encoding()) {
+        Ok(gimli::Operation::FrameOffset { offset }) if pc.is_empty() => {
+            Location::FramePointerOffset(offset.try_into().unwrap())
+        }
+        Ok(gimli::Operation::Address { address }) if pc.is_empty() => {
+            Location::Address(address.try_into().unwrap())
         }
+        _ => Location::Expression(expression),
+    };
+    Ok(Some(location))
+}
+
+/// Returns the DWARF expression held by `attr`, if it holds one.
+fn get_expression<R: Reader>(
+    attr: &gimli::Attribute<R>,
+    unit: &gimli::Unit<R>,
+) -> Option<DwarfExpression> {
+    match attr.value() {
+        gimli::AttributeValue::Exprloc(data) => Some(DwarfExpression {
+            bytes: data.0.to_slice().ok()?.into_owned(),
+            encoding: unit.encoding(),
+        }),
+        _ => None,
+    }
+}
+
+/// Returns the register number if `expression` is just DW_OP_reg<n>, as the locations of call
+/// site parameters are.
+fn single_register(expression: &DwarfExpression) -> Option<u16> {
+    let mut pc = gimli::EndianSlice::new(&expression.bytes, gimli::LittleEndian);
+    match gimli::Operation::parse(&mut pc, expression.encoding) {
+        Ok(gimli::Operation::Register { register }) if pc.is_empty() => Some(register.0),
+        _ => None,
+    }
+}
+
+/// Returns the value of a DW_AT_const_value: either a block of bytes, or an integer, which is
+/// stored in little endian order.
+fn get_const_value<R: Reader>(attr: &gimli::Attribute<R>) -> Option<Vec<u8>> {
+    match attr.value() {
+        gimli::AttributeValue::Block(data) => Some(data.to_slice().ok()?.into_owned()),
+        _ => attr
+            .sdata_value()
+            .or_else(|| attr.udata_value().map(|value| value as i64))
+            .map(|value| value.to_le_bytes().to_vec()),
     }
-    None
 }
 
 // based on dwarf_dump.rs
@@ -327,16 +851,11 @@ fn get_attr_value<R: Reader>(
             dump_exprloc(w, unit.encoding(), data)?;
             Ok(DebugValue::Str(w.to_string()))
         }
-        gimli::AttributeValue::UnitRef(offset) => {
-            match offset.to_unit_section_offset(unit) {
-                UnitSectionOffset::DebugInfoOffset(goff) => {
-                    Ok(DebugValue::Size(goff.0))
-                }
-                UnitSectionOffset::DebugTypesOffset(goff) => {
-                    Ok(DebugValue::Size(goff.0))
-                }
-            }
-        }
+        gimli::AttributeValue::UnitRef(offset) => match offset.to_unit_section_offset(unit) {
+            UnitSectionOffset::DebugInfoOffset(goff) => Ok(DebugValue::Size(goff.0)),
+            UnitSectionOffset::DebugTypesOffset(goff) => Ok(DebugValue::Size(goff.0)),
+        },
+        gimli::AttributeValue::DebugInfoRef(offset) => Ok(DebugValue::Size(offset.0)),
         gimli::AttributeValue::DebugStrRef(offset) => {
             if let Ok(s) = dwarf.debug_str.get_str(offset) {
                 Ok(DebugValue::Str(format!("{}", s.to_string_lossy()?)))
@@ -356,9 +875,7 @@ fn get_attr_value<R: Reader>(
             dump_file_index(w, value, unit, dwarf)?;
             Ok(DebugValue::Str(w.to_string()))
         }
-        _ => {
-            Ok(DebugValue::NoVal)
-        }
+        _ => Ok(DebugValue::NoVal),
     }
 }
 
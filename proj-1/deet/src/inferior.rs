@@ -1,19 +1,37 @@
+use nix::errno::Errno;
 use nix::sys::ptrace;
-use nix::sys::ptrace::{cont, getregs};
+use nix::sys::ptrace::{cont, getregs, Options};
 use nix::sys::signal;
 use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::Pid;
+use std::collections::VecDeque;
+use std::fs;
 use std::mem::size_of;
 use std::os::unix::process::CommandExt;
 use std::process::Child;
 use std::process::Command;
 
+use crate::core_file::{write_core, CoreFile, CoreThread};
 use crate::dwarf_data::DwarfData;
+use crate::unwind::{CallFrameInfo, Registers, Unwind};
+
+/// Backtraces give up after this many frames, in case the stack is corrupted in a way that makes
+/// unwinding go in circles.
+const MAX_FRAMES: usize = 1024;
 
 fn align_addr_to_word(addr: usize) -> usize {
     addr & (-(size_of::<usize>() as isize) as usize)
 }
 
+/// Returns the offset of debug register `index` (`u_debugreg[index]`) within `struct user`, which
+/// is what PTRACE_PEEKUSER and PTRACE_POKEUSER address.
+fn debug_register_offset(index: usize) -> usize {
+    let user: libc::user = unsafe { std::mem::zeroed() };
+    let base = &user as *const libc::user as usize;
+    let debugreg = &user.u_debugreg[index] as *const _ as usize;
+    debugreg - base
+}
+
 #[derive(Debug)]
 pub enum Status {
     /// Indicates inferior stopped. Contains the signal that stopped the process, as well as the
@@ -26,6 +44,33 @@ pub enum Status {
     /// Indicates the inferior exited due to a signal. Contains the signal that killed the
     /// process.
     Signaled(signal::Signal),
+
+    /// Indicates inferior stopped because a hardware watchpoint triggered. Contains the debug
+    /// register slot (0-3) that fired, as well as the current instruction pointer.
+    Watchpoint(usize, usize),
+
+    /// Indicates inferior stopped at a fork or exec, which the debugger has to act on before
+    /// resuming it. Contains the event, as well as the current instruction pointer.
+    Event(Event, usize),
+
+    /// Indicates inferior stopped on entry to or exit from a system call, which only happens while
+    /// system calls are being caught. Contains the system call number, the return value (None on
+    /// entry), as well as the current instruction pointer.
+    Syscall(u64, Option<i64>, usize),
+}
+
+/// A change to the inferior's processes that ptrace stops it for.
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    /// The inferior forked. Contains the new process, which is traced and stopped until the
+    /// debugger decides which of the two to follow, and whether it shares the inferior's memory
+    /// until it calls exec or exits (vfork).
+    Fork(Pid, bool),
+    /// The vfork child the inferior was waiting for has called exec or exited, so the inferior's
+    /// memory is its own again.
+    VforkDone,
+    /// The inferior called exec and is now at the start of the new program.
+    Exec,
 }
 
 /// This function calls ptrace with PTRACE_TRACEME to enable debugging on a process. You should use
@@ -37,11 +82,187 @@ fn child_traceme() -> Result<(), std::io::Error> {
     )))
 }
 
+/// A memory mapping of the inferior, i.e. one line of /proc/<pid>/maps.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    pub start: usize,
+    pub end: usize,
+    pub permissions: String,
+    /// Offset of the mapping within the mapped file
+    pub offset: usize,
+    /// The mapped file, or a pseudo-path like `[stack]`; None for anonymous mappings
+    pub path: Option<String>,
+}
+
+impl Mapping {
+    fn parse(line: &str) -> Option<Mapping> {
+        let mut fields = line.split_whitespace();
+        let mut range = fields.next()?.split('-');
+        let start = usize::from_str_radix(range.next()?, 16).ok()?;
+        let end = usize::from_str_radix(range.next()?, 16).ok()?;
+        let permissions = fields.next()?.to_string();
+        let offset = usize::from_str_radix(fields.next()?, 16).ok()?;
+        let path = fields.nth(2).map(|path| path.to_string());
+        Some(Mapping {
+            start,
+            end,
+            permissions,
+            offset,
+            path,
+        })
+    }
+}
+
+/// The general purpose registers, in the order `info registers` lists them.
+pub const REGISTER_NAMES: [&str; 27] = [
+    "rax", "rbx", "rcx", "rdx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip", "eflags", "cs", "ss", "ds", "es", "fs", "gs", "fs_base", "gs_base",
+    "orig_rax",
+];
+
+/// Returns the field of `regs` holding the register called `name`.
+fn register_mut<'a>(regs: &'a mut libc::user_regs_struct, name: &str) -> Option<&'a mut u64> {
+    Some(match name {
+        "rax" => &mut regs.rax,
+        "rbx" => &mut regs.rbx,
+        "rcx" => &mut regs.rcx,
+        "rdx" => &mut regs.rdx,
+        "rsi" => &mut regs.rsi,
+        "rdi" => &mut regs.rdi,
+        "rbp" | "fp" => &mut regs.rbp,
+        "rsp" | "sp" => &mut regs.rsp,
+        "r8" => &mut regs.r8,
+        "r9" => &mut regs.r9,
+        "r10" => &mut regs.r10,
+        "r11" => &mut regs.r11,
+        "r12" => &mut regs.r12,
+        "r13" => &mut regs.r13,
+        "r14" => &mut regs.r14,
+        "r15" => &mut regs.r15,
+        "rip" | "pc" => &mut regs.rip,
+        "eflags" => &mut regs.eflags,
+        "cs" => &mut regs.cs,
+        "ss" => &mut regs.ss,
+        "ds" => &mut regs.ds,
+        "es" => &mut regs.es,
+        "fs" => &mut regs.fs,
+        "gs" => &mut regs.gs,
+        "fs_base" => &mut regs.fs_base,
+        "gs_base" => &mut regs.gs_base,
+        "orig_rax" => &mut regs.orig_rax,
+        _ => return None,
+    })
+}
+
+/// Prints one line of a backtrace: the frame number, function and source location.
+pub fn print_frame(index: usize, frame: &Registers, debug_data: &DwarfData) {
+    // The rip of an outer frame is a return address; the call itself is on the line before it
+    let pc = if index > 0 { frame.rip - 1 } else { frame.rip };
+    let fn_name = debug_data
+        .get_function_from_addr(pc)
+        .unwrap_or_else(|| "??".to_string());
+    match debug_data.get_line_from_addr(pc) {
+        Some(line) => println!("#{:<2} {} {}", index, fn_name, line),
+        None => println!("#{:<2} {} {:#x}", index, fn_name, frame.rip),
+    }
+}
+
+/// What a thread of the inferior is doing, as far as we know.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ThreadState {
+    /// Stopped, waiting for us to resume it
+    Stopped,
+    /// Resumed, and hasn't reported a stop since
+    Running,
+    /// Just created by a clone we've been told about; its initial SIGSTOP hasn't arrived yet
+    Starting,
+}
+
+/// A thread (LWP) of the inferior.
+#[derive(Debug)]
+pub struct Thread {
+    /// The number `info threads` and `thread N` know the thread by, counting from 1 in order of
+    /// creation
+    pub id: usize,
+    pub tid: Pid,
+    state: ThreadState,
+    /// A SIGSTOP we sent to stop the thread is still on its way, because something else stopped
+    /// the thread first
+    sigstop_pending: bool,
+    /// The signal to deliver when the thread is next resumed
+    signal: Option<signal::Signal>,
+}
+
+/// The ptrace options every traced thread gets: report new threads and processes (which are then
+/// traced too) and calls to exec, and mark system call stops so they can't be mistaken for a
+/// SIGTRAP.
+fn trace_options() -> Options {
+    Options::PTRACE_O_TRACESYSGOOD
+        | Options::PTRACE_O_TRACECLONE
+        | Options::PTRACE_O_TRACEFORK
+        | Options::PTRACE_O_TRACEVFORK
+        | Options::PTRACE_O_TRACEVFORKDONE
+        | Options::PTRACE_O_TRACEEXEC
+}
+
+/// Returns the process (thread group) that thread `tid` belongs to.
+fn thread_group(tid: Pid) -> Option<Pid> {
+    let status = fs::read_to_string(format!("/proc/{}/status", tid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("Tgid:"))?;
+    Some(Pid::from_raw(line["Tgid:".len()..].trim().parse().ok()?))
+}
+
 pub struct Inferior {
-    child: Child,
+    pid: Pid,
+    /// The process we spawned, or None if we attached to a process that was already running or
+    /// followed a fork
+    child: Option<Child>,
+    /// Whether we attached to a process that was already running
+    attached: bool,
+    threads: Vec<Thread>,
+    next_thread_id: usize,
+    /// The thread registers are read from and stepping applies to: the one that last stopped, or
+    /// the one picked with `thread N`
+    current: Pid,
+    /// Whether the last resume was a continue of every thread rather than a step of the current
+    /// one. Threads created in the meantime are started the same way.
+    resuming_all: bool,
+    /// Stops that other threads reported while we were stopping them, to be reported before the
+    /// inferior runs again
+    deferred: VecDeque<WaitStatus>,
+    /// New processes that reported their first stop before the fork event that created them
+    early_children: Vec<Pid>,
+    /// The values of DR0-DR7 that every thread gets (DR4-DR6 are unused)
+    debug_registers: [usize; 8],
+    /// Whether continuing stops at every system call entry and exit (PTRACE_SYSCALL)
+    catch_syscalls: bool,
+    /// The core file this inferior was loaded from, if it isn't a live process. Its registers and
+    /// memory can be read but not changed, and it can't be run.
+    core: Option<CoreFile>,
 }
 
 impl Inferior {
+    /// Creates the Inferior for process `pid`, which is already traced and stopped. Its other
+    /// threads (if any) are added separately.
+    fn traced(pid: Pid, child: Option<Child>, attached: bool) -> Inferior {
+        let mut inferior = Inferior {
+            pid,
+            child,
+            attached,
+            threads: Vec::new(),
+            next_thread_id: 1,
+            current: pid,
+            resuming_all: false,
+            deferred: VecDeque::new(),
+            early_children: Vec::new(),
+            debug_registers: [0; 8],
+            catch_syscalls: false,
+            core: None,
+        };
+        inferior.add_thread(pid, ThreadState::Stopped);
+        inferior
+    }
+
     /// Attempts to start a new inferior process. Returns Some(Inferior) if successful, or None if
     /// an error is encountered.
     pub fn new(target: &str, args: &Vec<String>) -> Option<Inferior> {
@@ -51,109 +272,906 @@ impl Inferior {
             command.pre_exec(child_traceme); // fn child_traceme will run before exce
         }
         let child = command.spawn().ok()?; // child/inferior will *pause* because PTRACE_TRACEME
-        let inferior = Inferior { child };
+        let pid = Pid::from_raw(child.id() as i32);
+
+        // Wait for the SIGTRAP delivered on exec, so the child is stopped before we touch its
+        // memory
+        let stopped = match waitpid(pid, None) {
+            Ok(WaitStatus::Stopped(_, signal::Signal::SIGTRAP)) => {
+                ptrace::setoptions(pid, trace_options()).is_ok()
+            }
+            _ => false,
+        };
+        if !stopped {
+            // Don't leave the child running (or as a zombie) behind us. It may already be gone,
+            // in which case both of these fail harmlessly.
+            signal::kill(pid, signal::Signal::SIGKILL).ok();
+            waitpid(pid, None).ok();
+            return None;
+        }
+        Some(Inferior::traced(pid, Some(child), false))
+    }
+
+    /// Attaches to the already-running process `pid` with PTRACE_ATTACH and waits for it to stop.
+    /// PTRACE_ATTACH only applies to a single thread, so each of the process's threads is
+    /// attached to in turn.
+    pub fn attach(pid: Pid) -> Result<Inferior, nix::Error> {
+        let mut tids: Vec<Pid> = match fs::read_dir(format!("/proc/{}/task", pid)) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+                .map(Pid::from_raw)
+                .filter(|tid| *tid != pid)
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        tids.sort_by_key(|tid| tid.as_raw());
+        tids.insert(0, pid);
+
+        let mut inferior: Option<Inferior> = None;
+        for tid in tids {
+            if let Err(err) = ptrace::attach(tid) {
+                // The thread may have exited since we listed it
+                match inferior {
+                    Some(_) => continue,
+                    None => return Err(err),
+                }
+            }
+            // PTRACE_ATTACH sends a SIGSTOP; wait for it so the thread is stopped before we touch
+            // it. The SIGSTOP is swallowed here, since it isn't left pending for the next resume.
+            let stopped = waitpid(tid, Some(WaitPidFlag::__WALL))
+                .and_then(|_| ptrace::setoptions(tid, trace_options()));
+            if let Err(err) = stopped {
+                // Let go of the threads we already have, rather than leave them stopped
+                let _ = ptrace::detach(tid, None);
+                if let Some(inferior) = inferior.as_mut() {
+                    let _ = inferior.detach();
+                }
+                return Err(err);
+            }
+            match inferior.as_mut() {
+                Some(inferior) => inferior.add_thread(tid, ThreadState::Stopped),
+                None => inferior = Some(Inferior::traced(pid, None, true)),
+            }
+        }
+        Ok(inferior.unwrap())
+    }
+
+    /// Creates the Inferior for `pid`, a process the inferior forked. The new process is traced
+    /// (with the inferior's ptrace options) and stopped.
+    pub fn forked(pid: Pid) -> Inferior {
+        Inferior::traced(pid, None, false)
+    }
+
+    /// Creates an Inferior for the crashed process recorded in `core`, stopped in the thread that
+    /// crashed.
+    pub fn from_core(core: CoreFile) -> Inferior {
+        let mut inferior = Inferior::traced(core.threads[0].tid, None, false);
+        for thread in &core.threads[1..] {
+            inferior.add_thread(thread.tid, ThreadState::Stopped);
+        }
+        inferior.core = Some(core);
+        inferior
+    }
+
+    /// Returns true if this inferior is a core file rather than a live process.
+    pub fn is_core(&self) -> bool {
+        self.core.is_some()
+    }
+
+    /// Detaches from every thread of the inferior and lets it keep running. Breakpoints have to be
+    /// removed first.
+    pub fn detach(&mut self) -> Result<(), nix::Error> {
+        // Signals that stopped threads we haven't reported yet still have to be delivered
+        for status in self.deferred.drain(..).collect::<Vec<_>>() {
+            if let WaitStatus::Stopped(tid, signal) = status {
+                if let Some(thread) = self.threads.iter_mut().find(|thread| thread.tid == tid) {
+                    thread.signal = Some(signal);
+                }
+            }
+        }
+        for thread in &mut self.threads {
+            if thread.sigstop_pending {
+                // Let the SIGSTOP arrive now, or it would stop the process once we're gone
+                cont(thread.tid, None)?;
+                waitpid(thread.tid, Some(WaitPidFlag::__WALL))?;
+            }
+            ptrace::detach(thread.tid, thread.signal.take())?;
+        }
+        Ok(())
+    }
 
-        Some(inferior)
+    /// Returns true if this inferior was attached to rather than spawned by us.
+    pub fn is_attached(&self) -> bool {
+        self.attached
     }
 
     /// Returns the pid of this inferior.
     pub fn pid(&self) -> Pid {
-        nix::unistd::Pid::from_raw(self.child.id() as i32)
+        self.pid
+    }
+
+    /// Returns the inferior's threads, in order of creation.
+    pub fn threads(&self) -> &[Thread] {
+        &self.threads
+    }
+
+    /// Returns the thread that registers are read from and stepping applies to.
+    pub fn current_thread(&self) -> &Thread {
+        self.thread(self.current).unwrap()
     }
 
-    /// Calls waitpid on this inferior and returns a Status to indicate the state of the process
-    /// after the waitpid call.
-    pub fn wait(&self, options: Option<WaitPidFlag>) -> Result<Status, nix::Error> {
-        Ok(match waitpid(self.pid(), options)? {
-            WaitStatus::Exited(_pid, exit_code) => Status::Exited(exit_code),
-            WaitStatus::Signaled(_pid, signal, _core_dumped) => Status::Signaled(signal),
-            WaitStatus::Stopped(_pid, signal) => {
-                let regs = ptrace::getregs(self.pid())?;
-                Status::Stopped(signal, regs.rip as usize)
+    /// Makes the thread numbered `id` the current one. Returns false if there is no such thread.
+    pub fn select_thread(&mut self, id: usize) -> bool {
+        match self.threads.iter().find(|thread| thread.id == id) {
+            Some(thread) => {
+                self.current = thread.tid;
+                true
             }
-            other => panic!("waitpid returned unexpected status: {:?}", other),
-        })
+            None => false,
+        }
     }
 
-    pub fn continue_inferior(&self) -> Result<Status, nix::Error> {
-        cont(self.pid(), None)?; // wake inferior
-        self.wait(None) // wait inferior
+    /// Makes continuing stop at every system call entry and exit, or stop doing so.
+    pub fn set_catch_syscalls(&mut self, catch_syscalls: bool) {
+        self.catch_syscalls = catch_syscalls;
     }
 
-    pub fn step(&self) -> Result<Status, nix::Error> {
-        ptrace::step(self.pid(), None)?;
-        self.wait(None) // wait inferior
+    /// Returns the frame registers of thread `tid`.
+    pub fn thread_registers(&self, tid: Pid) -> Result<Registers, nix::Error> {
+        Ok(Registers::from_user_regs(&self.regs(tid)?))
     }
 
-    pub fn back_rip(&mut self) -> Result<(), nix::Error> {
-        let mut regs = getregs(self.pid()).unwrap();
+    /// Returns the general purpose registers of thread `tid`, from the core file if there is one.
+    fn regs(&self, tid: Pid) -> Result<libc::user_regs_struct, nix::Error> {
+        match &self.core {
+            Some(core) => core
+                .threads
+                .iter()
+                .find(|thread| thread.tid == tid)
+                .map(|thread| thread.regs)
+                .ok_or(nix::Error::Sys(Errno::ESRCH)),
+            None => getregs(tid),
+        }
+    }
+
+    fn thread(&self, tid: Pid) -> Option<&Thread> {
+        self.threads.iter().find(|thread| thread.tid == tid)
+    }
+
+    fn thread_mut(&mut self, tid: Pid) -> Option<&mut Thread> {
+        self.threads.iter_mut().find(|thread| thread.tid == tid)
+    }
+
+    fn add_thread(&mut self, tid: Pid, state: ThreadState) {
+        self.threads.push(Thread {
+            id: self.next_thread_id,
+            tid,
+            state,
+            sigstop_pending: false,
+            signal: None,
+        });
+        self.next_thread_id += 1;
+    }
+
+    /// Waits until some thread of the inferior stops in a way worth reporting, then stops all the
+    /// other threads, so that the whole inferior holds still while the user looks at it. New and
+    /// exiting threads are dealt with along the way.
+    pub fn wait(&mut self) -> Result<Status, nix::Error> {
+        loop {
+            let status = waitpid(Pid::from_raw(-1), Some(WaitPidFlag::__WALL))?;
+            match self.handle_wait_status(status)? {
+                Some(status @ Status::Exited(_)) | Some(status @ Status::Signaled(_)) => {
+                    return Ok(status)
+                }
+                Some(status) => {
+                    self.stop_threads()?;
+                    return Ok(status);
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// Deals with one status reported by waitpid. Returns the status to report to the user, or
+    /// None if the inferior has been resumed (or left running) and we should keep waiting.
+    fn handle_wait_status(&mut self, status: WaitStatus) -> Result<Option<Status>, nix::Error> {
+        match status {
+            WaitStatus::Exited(tid, exit_code) if tid == self.pid => {
+                Ok(Some(Status::Exited(exit_code)))
+            }
+            WaitStatus::Signaled(tid, signal, _core_dumped) if tid == self.pid => {
+                Ok(Some(Status::Signaled(signal)))
+            }
+            WaitStatus::Exited(tid, _) | WaitStatus::Signaled(tid, _, _) => {
+                if self.thread(tid).is_some() {
+                    self.thread_exited(tid)?;
+                }
+                Ok(None)
+            }
+            WaitStatus::Stopped(tid, signal) => {
+                let thread = match self.thread_mut(tid) {
+                    Some(thread) => thread,
+                    None => {
+                        // A thread or process whose creation we haven't been told about yet
+                        if thread_group(tid) == Some(self.pid) {
+                            println!("[New LWP {}]", tid);
+                            self.add_thread(tid, ThreadState::Stopped);
+                            self.start_thread(tid)?;
+                        } else {
+                            self.early_children.push(tid);
+                        }
+                        return Ok(None);
+                    }
+                };
+                if signal == signal::Signal::SIGSTOP && thread.state == ThreadState::Starting {
+                    thread.state = ThreadState::Stopped;
+                    self.start_thread(tid)?;
+                    return Ok(None);
+                }
+                thread.state = ThreadState::Stopped;
+                if signal == signal::Signal::SIGSTOP && thread.sigstop_pending {
+                    thread.sigstop_pending = false;
+                    self.resume_thread(tid)?;
+                    return Ok(None);
+                }
+                self.switch_to(tid);
+                let rip = getregs(tid)?.rip as usize;
+                Ok(Some(match signal {
+                    signal::Signal::SIGTRAP => match self.triggered_watchpoint()? {
+                        Some(slot) => Status::Watchpoint(slot, rip),
+                        None => Status::Stopped(signal, rip),
+                    },
+                    _ => Status::Stopped(signal, rip),
+                }))
+            }
+            WaitStatus::PtraceEvent(tid, _, event) => {
+                if let Some(thread) = self.thread_mut(tid) {
+                    thread.state = ThreadState::Stopped;
+                }
+                if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 {
+                    let new_tid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                    if self.thread(new_tid).is_none() {
+                        println!("[New LWP {}]", new_tid);
+                        self.add_thread(new_tid, ThreadState::Starting);
+                    }
+                    self.resume_thread(tid)?;
+                    return Ok(None);
+                }
+                let event = if event == ptrace::Event::PTRACE_EVENT_FORK as i32
+                    || event == ptrace::Event::PTRACE_EVENT_VFORK as i32
+                {
+                    let child = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                    // The new process starts out stopped by a SIGSTOP, which may have been seen
+                    // already
+                    match self.early_children.iter().position(|pid| *pid == child) {
+                        Some(index) => {
+                            self.early_children.remove(index);
+                        }
+                        None => {
+                            waitpid(child, Some(WaitPidFlag::__WALL))?;
+                        }
+                    }
+                    Event::Fork(child, event == ptrace::Event::PTRACE_EVENT_VFORK as i32)
+                } else if event == ptrace::Event::PTRACE_EVENT_VFORK_DONE as i32 {
+                    Event::VforkDone
+                } else if event == ptrace::Event::PTRACE_EVENT_EXEC as i32 {
+                    // exec gets rid of every other thread, and the thread that called it takes
+                    // over the process's pid
+                    let id = self.thread(self.pid).map_or(1, |thread| thread.id);
+                    self.threads.clear();
+                    self.add_thread(self.pid, ThreadState::Stopped);
+                    self.threads[0].id = id;
+                    self.current = self.pid;
+                    Event::Exec
+                } else {
+                    self.resume_thread(tid)?;
+                    return Ok(None);
+                };
+                let tid = if self.thread(tid).is_some() {
+                    tid
+                } else {
+                    self.pid
+                };
+                self.switch_to(tid);
+                Ok(Some(Status::Event(event, getregs(tid)?.rip as usize)))
+            }
+            WaitStatus::PtraceSyscall(tid) => {
+                if let Some(thread) = self.thread_mut(tid) {
+                    thread.state = ThreadState::Stopped;
+                }
+                self.switch_to(tid);
+                let regs = getregs(tid)?;
+                // The kernel sets rax to -ENOSYS before it runs the system call, so that is what
+                // we see on entry
+                let ret = if regs.rax as i64 == -(libc::ENOSYS as i64) {
+                    None
+                } else {
+                    Some(regs.rax as i64)
+                };
+                Ok(Some(Status::Syscall(regs.orig_rax, ret, regs.rip as usize)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Called when thread `tid` (not the main thread) exits. If it was being stepped, nothing is
+    /// left running, so the rest of the inferior is continued instead.
+    fn thread_exited(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        println!("[LWP {} exited]", tid);
+        let was_running = self.thread(tid).unwrap().state == ThreadState::Running;
+        self.threads.retain(|thread| thread.tid != tid);
+        if self.current == tid {
+            self.current = self.pid;
+            if was_running && !self.resuming_all {
+                self.resuming_all = true;
+                let stopped: Vec<Pid> = self
+                    .threads
+                    .iter()
+                    .filter(|thread| thread.state == ThreadState::Stopped)
+                    .map(|thread| thread.tid)
+                    .collect();
+                for tid in stopped {
+                    self.resume_thread(tid)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets up a new thread once it has reported its first stop: it gets the same watchpoints as
+    /// the others, and runs if the rest of the inferior is running.
+    fn start_thread(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        self.program_debug_registers(tid)?;
+        if self.resuming_all {
+            self.resume_thread(tid)?;
+        }
+        Ok(())
+    }
+
+    /// Resumes thread `tid` the way the inferior was last resumed: stepping if it is the thread
+    /// being stepped, continuing (up to the next system call, if those are caught) if every thread
+    /// was continued, and not at all otherwise.
+    fn resume_thread(&mut self, tid: Pid) -> Result<(), nix::Error> {
+        let (stepping, resuming_all) = (tid == self.current, self.resuming_all);
+        let catch_syscalls = self.catch_syscalls;
+        let thread = self.thread_mut(tid).unwrap();
+        let signal = thread.signal.take();
+        if resuming_all && catch_syscalls {
+            ptrace::syscall(tid, signal)?;
+        } else if resuming_all {
+            cont(tid, signal)?;
+        } else if stepping {
+            ptrace::step(tid, signal)?;
+        } else {
+            thread.signal = signal;
+            return Ok(());
+        }
+        thread.state = ThreadState::Running;
+        Ok(())
+    }
+
+    /// Makes `tid`, which just stopped, the current thread.
+    fn switch_to(&mut self, tid: Pid) {
+        if tid != self.current && self.threads.len() > 1 {
+            println!("[Switching to LWP {}]", tid);
+        }
+        self.current = tid;
+    }
+
+    /// Stops every thread that is still running. A thread that stops for some other reason
+    /// before our SIGSTOP arrives has that stop deferred, except that a thread that just hit a
+    /// breakpoint is simply rewound to hit it again later.
+    fn stop_threads(&mut self) -> Result<(), nix::Error> {
+        let mut waiting = Vec::new();
+        let mut gone = Vec::new();
+        for thread in &mut self.threads {
+            match thread.state {
+                ThreadState::Running => {
+                    let result = unsafe {
+                        libc::syscall(
+                            libc::SYS_tgkill,
+                            self.pid.as_raw(),
+                            thread.tid.as_raw(),
+                            libc::SIGSTOP,
+                        )
+                    };
+                    if result == -1 {
+                        // The thread has already exited, so there is nothing to wait for. The main
+                        // thread stays in the list until the whole process is reaped.
+                        match Errno::last() {
+                            Errno::ESRCH if thread.tid != self.pid => gone.push(thread.tid),
+                            Errno::ESRCH => (),
+                            errno => return Err(nix::Error::Sys(errno)),
+                        }
+                    } else {
+                        waiting.push(thread.tid);
+                    }
+                }
+                ThreadState::Starting => waiting.push(thread.tid),
+                ThreadState::Stopped => (),
+            }
+        }
+        for tid in gone {
+            println!("[LWP {} exited]", tid);
+            self.threads.retain(|thread| thread.tid != tid);
+        }
+
+        while let Some(tid) = waiting.pop() {
+            let starting = self.thread(tid).unwrap().state == ThreadState::Starting;
+            let status = waitpid(tid, Some(WaitPidFlag::__WALL))?;
+            match status {
+                WaitStatus::Exited(..) | WaitStatus::Signaled(..) if tid != self.pid => {
+                    println!("[LWP {} exited]", tid);
+                    self.threads.retain(|thread| thread.tid != tid);
+                    continue;
+                }
+                WaitStatus::Stopped(_, signal::Signal::SIGSTOP) => (),
+                WaitStatus::Stopped(_, signal::Signal::SIGTRAP)
+                    if self.rewind_breakpoint(tid)? =>
+                {
+                    self.thread_mut(tid).unwrap().sigstop_pending = !starting;
+                }
+                WaitStatus::PtraceEvent(_, _, event)
+                    if event == ptrace::Event::PTRACE_EVENT_CLONE as i32 =>
+                {
+                    let new_tid = Pid::from_raw(ptrace::getevent(tid)? as i32);
+                    if self.thread(new_tid).is_none() {
+                        println!("[New LWP {}]", new_tid);
+                        self.add_thread(new_tid, ThreadState::Starting);
+                        waiting.push(new_tid);
+                    }
+                    self.thread_mut(tid).unwrap().sigstop_pending = !starting;
+                }
+                status => {
+                    self.deferred.push_back(status);
+                    if let Some(thread) = self.thread_mut(tid) {
+                        thread.sigstop_pending = !starting;
+                    }
+                }
+            }
+            if let Some(thread) = self.thread_mut(tid) {
+                thread.state = ThreadState::Stopped;
+            }
+            if starting {
+                let resuming_all = std::mem::replace(&mut self.resuming_all, false);
+                self.start_thread(tid)?;
+                self.resuming_all = resuming_all;
+            }
+        }
+        Ok(())
+    }
+
+    /// If thread `tid` stopped because it executed an int3 (rather than because of a watchpoint),
+    /// moves it back onto the int3 and returns true.
+    fn rewind_breakpoint(&self, tid: Pid) -> Result<bool, nix::Error> {
+        if self.peek_user(tid, debug_register_offset(6))? & 0b1111 != 0 {
+            return Ok(false);
+        }
+        let mut regs = getregs(tid)?;
+        let addr = regs.rip as usize - 1;
+        let word = ptrace::read(tid, align_addr_to_word(addr) as ptrace::AddressType)? as u64;
+        if (word >> (8 * (addr - align_addr_to_word(addr)))) & 0xff != 0xcc {
+            return Ok(false);
+        }
         regs.rip -= 1;
-        ptrace::setregs(self.pid(), regs)
+        ptrace::setregs(tid, regs)?;
+        Ok(true)
+    }
+
+    /// Resumes the inferior, delivering `signal` to the current thread if given, and waits for it
+    /// to stop. A stop that was deferred while the inferior was being stopped last time is
+    /// reported first, without running anything.
+    pub fn continue_inferior(
+        &mut self,
+        signal: Option<signal::Signal>,
+    ) -> Result<Status, nix::Error> {
+        self.resuming_all = true;
+        self.thread_mut(self.current).unwrap().signal = signal;
+        while let Some(status) = self.deferred.pop_front() {
+            if let Some(status) = self.handle_wait_status(status)? {
+                return Ok(status);
+            }
+        }
+        let stopped: Vec<Pid> = self
+            .threads
+            .iter()
+            .filter(|thread| thread.state == ThreadState::Stopped)
+            .map(|thread| thread.tid)
+            .collect();
+        for tid in stopped {
+            self.resume_thread(tid)?; // wake inferior
+        }
+        self.wait() // wait inferior
     }
 
-    pub fn rip(&self) -> usize {
-        getregs(self.pid()).expect("get rip error").rip as usize
+    /// Executes one instruction in the current thread, delivering `signal` first if given. If the
+    /// signal has a handler, the thread stops at the handler's first instruction. The other
+    /// threads stay stopped.
+    pub fn step(&mut self, signal: Option<signal::Signal>) -> Result<Status, nix::Error> {
+        self.resuming_all = false;
+        self.thread_mut(self.current).unwrap().signal = signal;
+        self.resume_thread(self.current)?;
+        self.wait() // wait inferior
+    }
+
+    pub fn back_rip(&mut self) -> Result<(), nix::Error> {
+        let mut regs = self.regs(self.current)?;
+        regs.rip = regs.rip.wrapping_sub(1);
+        ptrace::setregs(self.current, regs)
+    }
+
+    pub fn rip(&self) -> Result<usize, nix::Error> {
+        Ok(self.regs(self.current)?.rip as usize)
+    }
+
+    pub fn rsp(&self) -> Result<usize, nix::Error> {
+        Ok(self.regs(self.current)?.rsp as usize)
+    }
+
+    pub fn rbp(&self) -> Result<usize, nix::Error> {
+        Ok(self.regs(self.current)?.rbp as usize)
+    }
+
+    /// Returns all of the current thread's general purpose registers.
+    pub fn registers(&self) -> Result<libc::user_regs_struct, nix::Error> {
+        self.regs(self.current)
+    }
+
+    /// Sets all of the current thread's general purpose registers.
+    pub fn set_registers(&self, regs: libc::user_regs_struct) -> Result<(), nix::Error> {
+        ptrace::setregs(self.current, regs)
+    }
+
+    /// Returns the value of the register called `name` (e.g. "rax"), or None if there is no such
+    /// register.
+    pub fn register(&self, name: &str) -> Result<Option<u64>, nix::Error> {
+        let mut regs = self.regs(self.current)?;
+        Ok(register_mut(&mut regs, name).map(|value| *value))
+    }
+
+    /// Returns the 16 bytes of register xmm`index`. Core files only give us the general purpose
+    /// registers, so this is None for them.
+    pub fn xmm_register(&self, index: usize) -> Result<Option<[u8; 16]>, nix::Error> {
+        if self.core.is_some() || index >= 16 {
+            return Ok(None);
+        }
+        let mut fpregs = std::mem::MaybeUninit::<libc::user_fpregs_struct>::uninit();
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_GETFPREGS,
+                self.current.as_raw(),
+                std::ptr::null_mut::<libc::c_void>(),
+                fpregs.as_mut_ptr(),
+            )
+        };
+        Errno::result(ret)?;
+        let fpregs = unsafe { fpregs.assume_init() };
+        let mut bytes = [0; 16];
+        for (i, word) in fpregs.xmm_space[index * 4..index * 4 + 4]
+            .iter()
+            .enumerate()
+        {
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&word.to_le_bytes());
+        }
+        Ok(Some(bytes))
+    }
+
+    /// Sets the register called `name` to `value`. Returns false if there is no such register.
+    pub fn set_register(&self, name: &str, value: u64) -> Result<bool, nix::Error> {
+        let mut regs = getregs(self.current)?;
+        match register_mut(&mut regs, name) {
+            Some(register) => *register = value,
+            None => return Ok(false),
+        }
+        ptrace::setregs(self.current, regs)?;
+        Ok(true)
+    }
+
+    pub fn read_word(&self, addr: usize) -> Result<usize, nix::Error> {
+        if let Some(core) = &self.core {
+            return match core.read(addr, size_of::<usize>()) {
+                Some(bytes) => {
+                    let mut word = [0; size_of::<usize>()];
+                    word.copy_from_slice(&bytes);
+                    Ok(usize::from_ne_bytes(word))
+                }
+                None => Err(nix::Error::Sys(Errno::EIO)),
+            };
+        }
+        Ok(ptrace::read(self.current, addr as ptrace::AddressType)? as usize)
+    }
+
+    /// Reads `len` bytes of the inferior's memory starting at `addr`, one word at a time.
+    pub fn read_bytes(&self, addr: usize, len: usize) -> Result<Vec<u8>, nix::Error> {
+        let mut bytes = Vec::with_capacity(len);
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + len {
+            let word = self.read_word(word_addr)?;
+            for (i, byte) in word.to_le_bytes().iter().enumerate() {
+                let byte_addr = word_addr + i;
+                if byte_addr >= addr && byte_addr < addr + len {
+                    bytes.push(*byte);
+                }
+            }
+            word_addr += size_of::<usize>();
+        }
+        Ok(bytes)
+    }
+
+    /// Reads a NUL-terminated string from the inferior, giving up after `max_len` bytes.
+    pub fn read_string(&self, addr: usize, max_len: usize) -> Result<String, nix::Error> {
+        let mut bytes = Vec::new();
+        while bytes.len() < max_len {
+            let word = self.read_word(addr + bytes.len())?;
+            for byte in word.to_le_bytes().iter() {
+                if *byte == 0 || bytes.len() == max_len {
+                    return Ok(String::from_utf8_lossy(&bytes).into_owned());
+                }
+                bytes.push(*byte);
+            }
+        }
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
     }
 
     pub fn write_byte(&mut self, addr: usize, val: u8) -> Result<u8, nix::Error> {
         let aligned_addr = align_addr_to_word(addr);
         let byte_offset = addr - aligned_addr;
-        let word = ptrace::read(self.pid(), aligned_addr as ptrace::AddressType)? as u64;
+        let word = ptrace::read(self.current, aligned_addr as ptrace::AddressType)? as u64;
         let orig_byte = (word >> 8 * byte_offset) & 0xff;
         let masked_word = word & !(0xff << 8 * byte_offset);
         let updated_word = masked_word | ((val as u64) << 8 * byte_offset);
         ptrace::write(
-            self.pid(),
+            self.current,
             aligned_addr as ptrace::AddressType,
             updated_word as *mut std::ffi::c_void,
         )?;
         Ok(orig_byte as u8)
     }
 
+    /// Writes `bytes` to the inferior's memory at `addr`. Words that are only partly overwritten
+    /// are read first so that their other bytes are kept.
+    pub fn write_bytes(&mut self, addr: usize, bytes: &[u8]) -> Result<(), nix::Error> {
+        let mut word_addr = align_addr_to_word(addr);
+        while word_addr < addr + bytes.len() {
+            let mut word = self.read_word(word_addr)?.to_le_bytes();
+            for (i, byte) in word.iter_mut().enumerate() {
+                let byte_addr = word_addr + i;
+                if byte_addr >= addr && byte_addr < addr + bytes.len() {
+                    *byte = bytes[byte_addr - addr];
+                }
+            }
+            ptrace::write(
+                self.current,
+                word_addr as ptrace::AddressType,
+                usize::from_le_bytes(word) as *mut std::ffi::c_void,
+            )?;
+            word_addr += size_of::<usize>();
+        }
+        Ok(())
+    }
+
+    fn peek_user(&self, tid: Pid, offset: usize) -> Result<usize, nix::Error> {
+        // PTRACE_PEEKUSER returns the data itself, so -1 is only an error if errno was set
+        unsafe { Errno::clear() };
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_PEEKUSER,
+                tid.as_raw(),
+                offset as *mut libc::c_void,
+                std::ptr::null_mut::<libc::c_void>(),
+            )
+        };
+        if ret == -1 && Errno::last() != Errno::UnknownErrno {
+            return Err(nix::Error::Sys(Errno::last()));
+        }
+        Ok(ret as usize)
+    }
+
+    fn poke_user(&self, tid: Pid, offset: usize, data: usize) -> Result<(), nix::Error> {
+        let ret = unsafe {
+            libc::ptrace(
+                libc::PTRACE_POKEUSER,
+                tid.as_raw(),
+                offset as *mut libc::c_void,
+                data as *mut libc::c_void,
+            )
+        };
+        Errno::result(ret).map(drop)
+    }
+
+    /// Programs debug register slot `slot` (0-3) of every thread to trap right after any write to
+    /// the `len` bytes at `addr`. `len` must be 1, 2, 4 or 8, and `addr` must be aligned to it.
+    pub fn set_watchpoint(
+        &mut self,
+        slot: usize,
+        addr: usize,
+        len: usize,
+    ) -> Result<(), nix::Error> {
+        // DR7 encodes the length as 00 = 1 byte, 01 = 2 bytes, 11 = 4 bytes, 10 = 8 bytes
+        let len_bits = match len {
+            1 => 0b00,
+            2 => 0b01,
+            4 => 0b11,
+            8 => 0b10,
+            _ => return Err(nix::Error::Sys(Errno::EINVAL)),
+        };
+        self.debug_registers[slot] = addr;
+        let dr7 = &mut self.debug_registers[7];
+        *dr7 &= !(0b11 << (slot * 2)) & !(0b1111 << (16 + slot * 4));
+        *dr7 |= 1 << (slot * 2); // local enable
+        *dr7 |= 0b01 << (16 + slot * 4); // trap on data writes
+        *dr7 |= len_bits << (18 + slot * 4);
+        for thread in &self.threads {
+            self.program_debug_registers(thread.tid)?;
+        }
+        Ok(())
+    }
+
+    /// Disables debug register slot `slot` in every thread.
+    pub fn clear_watchpoint(&mut self, slot: usize) -> Result<(), nix::Error> {
+        self.debug_registers[7] &= !(0b11 << (slot * 2)) & !(0b1111 << (16 + slot * 4));
+        for thread in &self.threads {
+            self.program_debug_registers(thread.tid)?;
+        }
+        Ok(())
+    }
+
+    /// Copies our debug register settings into thread `tid`. Each thread has its own debug
+    /// registers, and new threads start out without any watchpoints.
+    fn program_debug_registers(&self, tid: Pid) -> Result<(), nix::Error> {
+        for index in 0..4 {
+            self.poke_user(
+                tid,
+                debug_register_offset(index),
+                self.debug_registers[index],
+            )?;
+        }
+        self.poke_user(tid, debug_register_offset(7), self.debug_registers[7])
+    }
+
+    /// Checks the current thread's DR6 to see whether a watchpoint caused the last SIGTRAP, and
+    /// resets it so the next trap starts clean.
+    fn triggered_watchpoint(&self) -> Result<Option<usize>, nix::Error> {
+        let dr6 = self.peek_user(self.current, debug_register_offset(6))?;
+        let slot = (0..4).find(|slot| dr6 & (1 << slot) != 0);
+        if slot.is_some() {
+            self.poke_user(self.current, debug_register_offset(6), 0)?;
+        }
+        Ok(slot)
+    }
+
     pub fn print(&self, status: &Status, debug_data: &DwarfData) {
         match status {
             Status::Exited(exit_code) => println!("Child exit (status {}) ", exit_code),
             Status::Stopped(signal, line) => {
                 println!("Child stop (signal {})", signal);
-                match debug_data.get_line_from_addr(*line) {
-                    Some(location) => println!("Stopped at {}", location),
-                    None => (),
-                }
+                self.print_location(*line, debug_data);
             }
             Status::Signaled(signal) => println!("signal: {}", signal),
+            Status::Watchpoint(_, line) | Status::Event(_, line) | Status::Syscall(_, _, line) => {
+                self.print_location(*line, debug_data)
+            }
         }
     }
 
-    pub fn kill(&mut self) {
-        self.child.kill().expect("kill process failed");
-        println!("kill running inferior (pid {})", self.pid());
+    /// Prints the source location of `addr`, falling back to the function name (or the raw
+    /// address) when there is no line information for it.
+    pub fn print_location(&self, addr: usize, debug_data: &DwarfData) {
+        match debug_data.get_line_from_addr(addr) {
+            Some(location) => println!("Stopped at {}", location),
+            None => match debug_data.get_function_from_addr(addr) {
+                Some(func) => println!("Stopped at {:#x} in {}", addr, func),
+                None => println!("Stopped at {:#x}", addr),
+            },
+        }
     }
 
-    pub fn print_backtrace(&self, debug_data: &DwarfData) -> Result<(), nix::Error> {
-        let pid = self.pid();
-        let rip = getregs(pid)?.rip;
-        let mut rbp = getregs(pid)?.rbp;
+    /// Writes a core file of the inferior to `path`, with the current thread first. `signal` is
+    /// the signal it is stopped with.
+    pub fn write_core(&self, path: &str, signal: Option<signal::Signal>) -> std::io::Result<()> {
+        let mut threads = Vec::new();
+        for thread in &self.threads {
+            let regs = self
+                .regs(thread.tid)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err.to_string()))?;
+            let thread = CoreThread {
+                tid: thread.tid,
+                signal: if thread.tid == self.current {
+                    signal
+                } else {
+                    None
+                },
+                regs,
+            };
+            if thread.tid == self.current {
+                threads.insert(0, thread);
+            } else {
+                threads.push(thread);
+            }
+        }
+        write_core(path, self.pid, &threads, &self.memory_maps()?)
+    }
 
-        let mut instruction_ptr = rip as usize;
-        let mut base_ptr = rbp as usize;
+    pub fn kill(&mut self) {
+        let result = match self.child.as_mut() {
+            Some(child) => child.kill().map_err(|err| err.to_string()),
+            None => match signal::kill(self.pid(), signal::Signal::SIGKILL) {
+                Ok(()) => {
+                    // We aren't the parent, but as the tracer we still get to see it die
+                    let _ = waitpid(self.pid(), Some(WaitPidFlag::__WALL));
+                    Ok(())
+                }
+                Err(err) => Err(err.to_string()),
+            },
+        };
+        match result {
+            Ok(()) => println!("kill running inferior (pid {})", self.pid()),
+            // It may already be gone
+            Err(err) => println!("Error killing inferior (pid {}): {}", self.pid(), err),
+        }
+    }
 
-        let mut line;
-        let mut fn_name;
+    /// Returns the inferior's memory mappings, as listed in /proc/<pid>/maps (or as recorded in the
+    /// core file).
+    pub fn memory_maps(&self) -> Result<Vec<Mapping>, std::io::Error> {
+        if let Some(core) = &self.core {
+            return Ok(core.mappings.clone());
+        }
+        let maps = fs::read_to_string(format!("/proc/{}/maps", self.pid()))?;
+        Ok(maps.lines().filter_map(Mapping::parse).collect())
+    }
 
-        loop {
-            line = debug_data.get_line_from_addr(instruction_ptr).unwrap();
-            fn_name = debug_data.get_function_from_addr(instruction_ptr).unwrap();
-            println!("{} {}", fn_name, line);
+    /// Unwinds the stack using the CFI tables of the target and of any shared libraries the frames
+    /// are in. Returns the registers of every frame, innermost first. Unwinding stops at the
+    /// outermost frame, or at the first frame we have no CFI for.
+    pub fn unwind_stack(&self, debug_data: &DwarfData) -> Result<Vec<Registers>, nix::Error> {
+        let mut frames = vec![Registers::from_user_regs(&self.regs(self.current)?)];
+        let maps = self.memory_maps().unwrap_or_default();
+        // CFI of shared libraries, loaded as the stack turns out to go through them
+        let mut libraries: Vec<(&str, Option<CallFrameInfo>)> = Vec::new();
+        let read_word = |addr| self.read_word(addr).ok();
 
-            if fn_name == "main".to_string() {
-                break;
+        while frames.len() < MAX_FRAMES {
+            let frame = frames[frames.len() - 1];
+            let is_innermost = frames.len() == 1;
+            let mut unwind = debug_data
+                .call_frame_info()
+                .unwind(&frame, is_innermost, read_word);
+            if let Unwind::Unknown = unwind {
+                if let Some(mapping) = maps
+                    .iter()
+                    .find(|m| m.start <= frame.rip && frame.rip < m.end)
+                {
+                    if let Some(path) = mapping.path.as_deref() {
+                        if !libraries.iter().any(|(name, _)| *name == path) {
+                            let start = maps
+                                .iter()
+                                .filter(|m| m.path.as_deref() == Some(path))
+                                .map(|m| m.start - m.offset)
+                                .min()
+                                .unwrap_or(mapping.start);
+                            libraries.push((path, CallFrameInfo::from_file(path, start)));
+                        }
+                        let (_, cfi) = libraries.iter().find(|(name, _)| *name == path).unwrap();
+                        if let Some(cfi) = cfi {
+                            unwind = cfi.unwind(&frame, is_innermost, read_word);
+                        }
+                    }
+                }
+            }
+            match unwind {
+                // The caller's frame is always further up the stack; anything else means we have
+                // lost track
+                Unwind::Caller(caller) if caller.rsp > frame.rsp => frames.push(caller),
+                _ => break,
             }
-
-            rbp += 8;
-            instruction_ptr = ptrace::read(pid, (base_ptr + 8) as ptrace::AddressType)? as usize;
-            base_ptr = ptrace::read(pid, base_ptr as ptrace::AddressType)? as usize;
         }
+        Ok(frames)
+    }
 
-        Ok(())
+    pub fn print_backtrace(&self, frames: &[Registers], debug_data: &DwarfData) {
+        for (index, frame) in frames.iter().enumerate() {
+            print_frame(index, frame, debug_data);
+        }
     }
 }
@@ -0,0 +1,389 @@
+//! Evaluation of DWARF location descriptions against a running (or dumped) process. Optimized
+//! code moves variables between registers and the stack as it runs, describes them with location
+//! lists and DW_OP_piece, and recovers parameters whose registers were reused through
+//! DW_OP_entry_value; all of that is resolved here using the registers and memory of one frame.
+
+use crate::dwarf_data::{DwarfExpression, Location};
+use gimli::EvaluationResult;
+
+type Slice<'a> = gimli::EndianSlice<'a, gimli::LittleEndian>;
+
+/// The error for values the debug info says are (at this pc) gone
+pub const OPTIMIZED_OUT: &str = "value has been optimized out";
+
+/// The general purpose registers by DWARF register number, which is not the order the hardware
+/// encodes them in
+const DWARF_REGISTERS: [&str; 17] = [
+    "rax", "rdx", "rcx", "rbx", "rsi", "rdi", "rbp", "rsp", "r8", "r9", "r10", "r11", "r12", "r13",
+    "r14", "r15", "rip",
+];
+
+/// What evaluating a location needs to know about the frame it is evaluated in.
+pub trait Frame {
+    /// Returns the contents of DWARF register `register` in this frame, or None if they are lost,
+    /// as caller-saved registers of outer frames are.
+    fn register(&self, register: u16) -> Option<Vec<u8>>;
+    fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String>;
+    /// Returns the canonical frame address: the value of rsp before the call that created the
+    /// frame.
+    fn cfa(&self) -> Option<usize>;
+    /// Returns the frame base of the function, which DW_OP_fbreg offsets are relative to.
+    fn frame_base(&self) -> Option<usize>;
+    /// Returns the value DWARF register `register` had when the function was entered.
+    fn entry_value(&self, register: u16) -> Option<u64>;
+}
+
+/// Returns the name of the general purpose register with DWARF number `register`.
+pub fn register_name(register: u16) -> Option<&'static str> {
+    DWARF_REGISTERS.get(register as usize).copied()
+}
+
+/// Reads the `size`-byte value that `location` describes while the frame is at `pc`. Returns its
+/// bytes, and its address if it lives in memory in one piece (so it can be assigned to or
+/// watched).
+pub fn read_location(
+    location: &Location,
+    pc: usize,
+    size: usize,
+    frame: &dyn Frame,
+) -> Result<(Vec<u8>, Option<usize>), String> {
+    match location {
+        Location::Address(addr) => Ok((frame.read_memory(*addr, size)?, Some(*addr))),
+        Location::FramePointerOffset(offset) => {
+            let frame_base = frame
+                .frame_base()
+                .ok_or_else(|| "Could not find the frame base.".to_string())?;
+            let addr = (frame_base as isize + offset) as usize;
+            Ok((frame.read_memory(addr, size)?, Some(addr)))
+        }
+        Location::Expression(expression) => read_expression(expression, size, frame),
+        Location::List(entries) => {
+            match entries
+                .iter()
+                .find(|(start, end, _)| *start <= pc && pc < *end)
+            {
+                Some((_, _, expression)) => read_expression(expression, size, frame),
+                None => Err(OPTIMIZED_OUT.to_string()),
+            }
+        }
+        Location::Constant(bytes) => {
+            let mut bytes = bytes.clone();
+            bytes.resize(size, 0);
+            Ok((bytes, None))
+        }
+        Location::OptimizedOut => Err(OPTIMIZED_OUT.to_string()),
+    }
+}
+
+/// Evaluates an expression that computes a value rather than a location, such as a frame base or
+/// the argument a call site passes.
+pub fn expression_value(expression: &DwarfExpression, frame: &dyn Frame) -> Result<u64, String> {
+    let pieces = evaluate(expression, frame)?;
+    let location = match pieces.as_slice() {
+        [piece] if piece.size_in_bits.is_none() => &piece.location,
+        _ => return Err("Unsupported DWARF expression".to_string()),
+    };
+    match location {
+        // Without DW_OP_stack_value, the value is left as if it were an address
+        gimli::Location::Address { address } => Ok(*address),
+        gimli::Location::Register { register } => frame
+            .register(register.0)
+            .map(|bytes| to_u64(&bytes))
+            .ok_or_else(|| OPTIMIZED_OUT.to_string()),
+        gimli::Location::Value { value } => Ok(value.to_u64(!0).unwrap_or(0)),
+        _ => Err(OPTIMIZED_OUT.to_string()),
+    }
+}
+
+/// Reads the value `expression` locates, assembling it from its pieces.
+fn read_expression(
+    expression: &DwarfExpression,
+    size: usize,
+    frame: &dyn Frame,
+) -> Result<(Vec<u8>, Option<usize>), String> {
+    let pieces = evaluate(expression, frame)?;
+    if let [piece] = pieces.as_slice() {
+        if let (None, gimli::Location::Address { address }) = (piece.size_in_bits, &piece.location)
+        {
+            let addr = *address as usize;
+            return Ok((frame.read_memory(addr, size)?, Some(addr)));
+        }
+    }
+
+    let mut bytes = Vec::new();
+    for piece in &pieces {
+        let len = match piece.size_in_bits {
+            Some(bits) => (bits as usize + 7) / 8,
+            None => size,
+        };
+        let mut contents = match &piece.location {
+            gimli::Location::Register { register } => frame
+                .register(register.0)
+                .ok_or_else(|| OPTIMIZED_OUT.to_string())?,
+            gimli::Location::Address { address } => frame.read_memory(*address as usize, len)?,
+            gimli::Location::Value { value } => match value {
+                gimli::Value::F32(value) => value.to_le_bytes().to_vec(),
+                gimli::Value::F64(value) => value.to_le_bytes().to_vec(),
+                _ => value.to_u64(!0).unwrap_or(0).to_le_bytes().to_vec(),
+            },
+            gimli::Location::Bytes { value } => value.slice().to_vec(),
+            // Empty pieces are parts of the value that no longer exist anywhere, and we can't
+            // show the object an implicit pointer points to
+            gimli::Location::Empty | gimli::Location::ImplicitPointer { .. } => {
+                return Err(OPTIMIZED_OUT.to_string())
+            }
+        };
+        contents.resize(len, 0);
+        bytes.extend(contents);
+    }
+    if bytes.len() < size {
+        return Err(OPTIMIZED_OUT.to_string());
+    }
+    bytes.truncate(size);
+    Ok((bytes, None))
+}
+
+/// Runs `expression`, supplying registers, memory and whatever else it asks for from `frame`.
+fn evaluate<'a>(
+    expression: &'a DwarfExpression,
+    frame: &dyn Frame,
+) -> Result<Vec<gimli::Piece<Slice<'a>>>, String> {
+    let bytecode = gimli::Expression(Slice::new(&expression.bytes, gimli::LittleEndian));
+    let mut evaluation = bytecode.evaluation(expression.encoding);
+    let mut result = evaluation.evaluate();
+    loop {
+        let next = match result.map_err(|err| format!("Invalid DWARF expression: {}", err))? {
+            EvaluationResult::Complete => return Ok(evaluation.result()),
+            EvaluationResult::RequiresMemory { address, size, .. } => {
+                let bytes = frame.read_memory(address as usize, size as usize)?;
+                evaluation.resume_with_memory(gimli::Value::Generic(to_u64(&bytes)))
+            }
+            EvaluationResult::RequiresRegister { register, .. } => {
+                let bytes = frame
+                    .register(register.0)
+                    .ok_or_else(|| OPTIMIZED_OUT.to_string())?;
+                evaluation.resume_with_register(gimli::Value::Generic(to_u64(&bytes)))
+            }
+            EvaluationResult::RequiresFrameBase => {
+                let frame_base = frame
+                    .frame_base()
+                    .ok_or_else(|| "Could not find the frame base.".to_string())?;
+                evaluation.resume_with_frame_base(frame_base as u64)
+            }
+            EvaluationResult::RequiresCallFrameCfa => {
+                let cfa = frame
+                    .cfa()
+                    .ok_or_else(|| "Could not find the canonical frame address.".to_string())?;
+                evaluation.resume_with_call_frame_cfa(cfa as u64)
+            }
+            EvaluationResult::RequiresRelocatedAddress(address) => {
+                evaluation.resume_with_relocated_address(address)
+            }
+            EvaluationResult::RequiresEntryValue(entry) => {
+                // Only an entry value that is a register can be recovered, and then only if the
+                // caller's call site says what it passed there
+                let mut operand = entry.0;
+                let value = match gimli::Operation::parse(&mut operand, expression.encoding) {
+                    Ok(gimli::Operation::Register { register }) if operand.is_empty() => {
+                        frame.entry_value(register.0)
+                    }
+                    _ => None,
+                };
+                let value = value.ok_or_else(|| OPTIMIZED_OUT.to_string())?;
+                evaluation.resume_with_entry_value(gimli::Value::Generic(value))
+            }
+            _ => return Err("Unsupported DWARF expression".to_string()),
+        };
+        result = next;
+    }
+}
+
+/// Reads up to 8 little endian bytes as an integer.
+fn to_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0; 8];
+    let len = bytes.len().min(8);
+    word[..len].copy_from_slice(&bytes[..len]);
+    u64::from_le_bytes(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMORY: usize = 0x2000;
+
+    /// A frame with rax and rbp set, 32 bytes of memory at 0x2000 and rdi as the only register
+    /// with an entry value.
+    struct TestFrame {
+        memory: Vec<u8>,
+    }
+
+    impl TestFrame {
+        fn new() -> TestFrame {
+            TestFrame {
+                memory: (0..32).collect(),
+            }
+        }
+    }
+
+    impl Frame for TestFrame {
+        fn register(&self, register: u16) -> Option<Vec<u8>> {
+            let value: u64 = match register_name(register)? {
+                "rax" => 0x1122_3344,
+                "rbp" => 0x2010,
+                "rsp" => 0x2000,
+                _ => return None,
+            };
+            Some(value.to_le_bytes().to_vec())
+        }
+
+        fn read_memory(&self, addr: usize, len: usize) -> Result<Vec<u8>, String> {
+            addr.checked_sub(MEMORY)
+                .and_then(|start| self.memory.get(start..start + len))
+                .map(|bytes| bytes.to_vec())
+                .ok_or_else(|| format!("Cannot access memory at address {:#x}", addr))
+        }
+
+        fn cfa(&self) -> Option<usize> {
+            Some(0x2020)
+        }
+
+        fn frame_base(&self) -> Option<usize> {
+            Some(0x2010)
+        }
+
+        fn entry_value(&self, register: u16) -> Option<u64> {
+            match register_name(register)? {
+                "rdi" => Some(99),
+                _ => None,
+            }
+        }
+    }
+
+    fn expression(bytes: &[u8]) -> DwarfExpression {
+        DwarfExpression {
+            bytes: bytes.to_vec(),
+            encoding: gimli::Encoding {
+                format: gimli::Format::Dwarf32,
+                version: 4,
+                address_size: 8,
+            },
+        }
+    }
+
+    fn read(location: &Location, size: usize) -> Result<(Vec<u8>, Option<usize>), String> {
+        read_location(location, 0x401000, size, &TestFrame::new())
+    }
+
+    #[test]
+    fn simple_locations() {
+        assert_eq!(
+            read(&Location::Address(0x2004), 4),
+            Ok((vec![4, 5, 6, 7], Some(0x2004)))
+        );
+        assert_eq!(
+            read(&Location::FramePointerOffset(-8), 2),
+            Ok((vec![8, 9], Some(0x2008)))
+        );
+        assert_eq!(
+            read(&Location::Constant(vec![1, 2]), 4),
+            Ok((vec![1, 2, 0, 0], None))
+        );
+        assert_eq!(
+            read(&Location::OptimizedOut, 4),
+            Err(OPTIMIZED_OUT.to_string())
+        );
+        assert!(read(&Location::Address(0x3000), 4).is_err());
+    }
+
+    #[test]
+    fn expression_locations() {
+        // DW_OP_reg0 (rax)
+        assert_eq!(
+            read(&Location::Expression(expression(&[0x50])), 4),
+            Ok((vec![0x44, 0x33, 0x22, 0x11], None))
+        );
+        // DW_OP_fbreg -16
+        assert_eq!(
+            read(&Location::Expression(expression(&[0x91, 0x70])), 2),
+            Ok((vec![0, 1], Some(0x2000)))
+        );
+        // DW_OP_breg7 (rsp) 8
+        assert_eq!(
+            read(&Location::Expression(expression(&[0x77, 0x08])), 1),
+            Ok((vec![8], Some(0x2008)))
+        );
+        // DW_OP_call_frame_cfa, DW_OP_lit4, DW_OP_minus
+        assert_eq!(
+            read(&Location::Expression(expression(&[0x9c, 0x34, 0x1c])), 1),
+            Ok((vec![28], Some(0x201c)))
+        );
+        // DW_OP_addr 0x2001
+        let addr = expression(&[0x03, 0x01, 0x20, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            read(&Location::Expression(addr), 1),
+            Ok((vec![1], Some(0x2001)))
+        );
+        // DW_OP_reg3 (rbx), which this frame lost
+        assert_eq!(
+            read(&Location::Expression(expression(&[0x53])), 8),
+            Err(OPTIMIZED_OUT.to_string())
+        );
+    }
+
+    #[test]
+    fn pieces_are_assembled() {
+        // DW_OP_reg0, DW_OP_piece 2, DW_OP_lit7, DW_OP_stack_value, DW_OP_piece 2
+        let pieces = expression(&[0x50, 0x93, 0x02, 0x37, 0x9f, 0x93, 0x02]);
+        assert_eq!(
+            read(&Location::Expression(pieces), 4),
+            Ok((vec![0x44, 0x33, 7, 0], None))
+        );
+        // DW_OP_piece 2 (gone), DW_OP_reg0, DW_OP_piece 2
+        let missing = expression(&[0x93, 0x02, 0x50, 0x93, 0x02]);
+        assert_eq!(
+            read(&Location::Expression(missing), 4),
+            Err(OPTIMIZED_OUT.to_string())
+        );
+    }
+
+    #[test]
+    fn location_list_depends_on_pc() {
+        let list = Location::List(vec![
+            (0x401000, 0x401010, expression(&[0x50])),
+            (0x401010, 0x401020, expression(&[0x91, 0x70])),
+        ]);
+        let frame = TestFrame::new();
+        assert_eq!(
+            read_location(&list, 0x401008, 1, &frame),
+            Ok((vec![0x44], None))
+        );
+        assert_eq!(
+            read_location(&list, 0x401010, 1, &frame),
+            Ok((vec![0], Some(0x2000)))
+        );
+        assert_eq!(
+            read_location(&list, 0x401020, 1, &frame),
+            Err(OPTIMIZED_OUT.to_string())
+        );
+    }
+
+    #[test]
+    fn entry_values() {
+        let frame = TestFrame::new();
+        // DW_OP_entry_value(DW_OP_reg5 (rdi)), DW_OP_stack_value
+        let rdi = expression(&[0xa3, 0x01, 0x55, 0x9f]);
+        assert_eq!(expression_value(&rdi, &frame), Ok(99));
+        // The same for rsi, whose entry value isn't known
+        let rsi = expression(&[0xa3, 0x01, 0x54, 0x9f]);
+        assert_eq!(
+            expression_value(&rsi, &frame),
+            Err(OPTIMIZED_OUT.to_string())
+        );
+        // DW_OP_breg6 (rbp) 0
+        assert_eq!(
+            expression_value(&expression(&[0x76, 0x00]), &frame),
+            Ok(0x2010)
+        );
+    }
+}
@@ -1,24 +1,69 @@
+mod breakpoint;
+mod core_file;
 mod debugger;
 mod debugger_command;
+mod disassemble;
 mod dwarf_data;
+mod expression;
 mod gimli_wrapper;
 mod inferior;
+mod location;
+mod record;
+mod signal_policy;
+mod source;
+mod syscall;
+mod unwind;
 
 use crate::debugger::Debugger;
 use nix::sys::signal::{signal, SigHandler, Signal};
+use nix::unistd::Pid;
 use std::env;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <target program>", args[0]);
-        std::process::exit(1);
-    }
-    let target = &args[1];
+    let (target, pid, core) = match args.len() {
+        2 => (args[1].clone(), None, None),
+        3 | 4 if args[1] == "--pid" => {
+            let pid = match args[2].parse::<i32>() {
+                Ok(pid) => Pid::from_raw(pid),
+                Err(_) => {
+                    println!("Invalid pid {}", args[2]);
+                    std::process::exit(1);
+                }
+            };
+            // Without an explicit target, debug whatever the process is running
+            let target = match args.get(3) {
+                Some(target) => target.clone(),
+                None => match std::fs::read_link(format!("/proc/{}/exe", pid)) {
+                    Ok(exe) => exe.to_string_lossy().into_owned(),
+                    Err(err) => {
+                        println!("Could not find the executable of process {}: {}", pid, err);
+                        std::process::exit(1);
+                    }
+                },
+            };
+            (target, Some(pid), None)
+        }
+        3 => (args[1].clone(), None, Some(args[2].clone())),
+        _ => {
+            println!(
+                "Usage: {0} <target program> [core file]\n       {0} --pid <pid> [target program]",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
 
     // Disable handling of ctrl+c in this process (so that ctrl+c only gets delivered to child
     // processes)
     unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) }.expect("Error disabling SIGINT handling");
 
-    Debugger::new(target).run();
+    let mut debugger = Debugger::new(&target);
+    if let Some(pid) = pid {
+        debugger.attach(pid);
+    }
+    if let Some(core) = core {
+        debugger.load_core(&core);
+    }
+    debugger.run();
 }
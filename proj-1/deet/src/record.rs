@@ -0,0 +1,126 @@
+//! The execution log behind `record` and the reverse commands. While recording, the inferior is
+//! single-stepped, and before each instruction runs we save the registers and whatever memory it
+//! is about to write. Undoing an instruction puts those back.
+//!
+//! Only what the instruction itself changes is saved, so the floating point and vector registers
+//! are not restored. The memory the kernel writes during a system call isn't known either, so the
+//! log is emptied whenever one is made: the reverse commands stop there.
+
+use crate::disassemble::Flow;
+use std::collections::VecDeque;
+
+/// How many instructions the log holds before the oldest ones are dropped
+pub const DEFAULT_LIMIT: usize = 200_000;
+
+/// One executed instruction, and the state it changed as it was before it ran.
+pub struct Entry {
+    pub regs: libc::user_regs_struct,
+    /// The memory the instruction wrote, as `(address, old contents)` pairs
+    pub memory: Vec<(usize, Vec<u8>)>,
+    /// Whether the instruction was a call, a return or a system call
+    pub flow: Flow,
+}
+
+/// The instructions executed since `record`, oldest first.
+pub struct Recording {
+    entries: VecDeque<Entry>,
+    limit: usize,
+}
+
+impl Recording {
+    pub fn new(limit: usize) -> Recording {
+        Recording {
+            entries: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Logs an executed instruction, dropping the oldest one if the log is full.
+    pub fn push(&mut self, entry: Entry) {
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Removes the most recently executed instruction from the log and returns it, so it can be
+    /// undone.
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.entries.pop_back()
+    }
+
+    /// Returns the most recently executed instruction.
+    pub fn last(&self) -> Option<&Entry> {
+        self.entries.back()
+    }
+
+    /// Forgets every logged instruction, so none of them can be undone.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(rip: u64) -> Entry {
+        let mut regs: libc::user_regs_struct = unsafe { std::mem::zeroed() };
+        regs.rip = rip;
+        Entry {
+            regs,
+            memory: vec![(rip as usize, vec![0xcc])],
+            flow: Flow::Other,
+        }
+    }
+
+    #[test]
+    fn pops_most_recent_first() {
+        let mut recording = Recording::new(DEFAULT_LIMIT);
+        for rip in 1..=3 {
+            recording.push(entry(rip));
+        }
+        assert_eq!(recording.last().map(|entry| entry.regs.rip), Some(3));
+        let popped: Vec<u64> = std::iter::from_fn(|| recording.pop())
+            .map(|entry| entry.regs.rip)
+            .collect();
+        assert_eq!(popped, vec![3, 2, 1]);
+        assert_eq!(recording.len(), 0);
+        assert!(recording.pop().is_none());
+    }
+
+    #[test]
+    fn evicts_oldest_past_limit() {
+        let mut recording = Recording::new(DEFAULT_LIMIT);
+        for rip in 0..DEFAULT_LIMIT as u64 + 5 {
+            recording.push(entry(rip));
+        }
+        assert_eq!(recording.len(), DEFAULT_LIMIT);
+        assert_eq!(recording.limit(), DEFAULT_LIMIT);
+        assert_eq!(
+            recording.last().map(|entry| entry.regs.rip),
+            Some(DEFAULT_LIMIT as u64 + 4)
+        );
+        // The five oldest instructions were dropped
+        let oldest = std::iter::from_fn(|| recording.pop()).last().unwrap();
+        assert_eq!(oldest.regs.rip, 5);
+    }
+
+    #[test]
+    fn clear_empties_log() {
+        let mut recording = Recording::new(2);
+        recording.push(entry(1));
+        recording.push(entry(2));
+        recording.clear();
+        assert_eq!(recording.len(), 0);
+        assert!(recording.last().is_none());
+    }
+}
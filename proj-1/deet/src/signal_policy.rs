@@ -0,0 +1,173 @@
+//! The `handle` command's table of what to do with each signal the inferior receives: whether to
+//! stop, whether to say so, and whether to deliver it.
+
+use nix::sys::signal::Signal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// What to do when the inferior receives a signal.
+#[derive(Debug, Clone, Copy)]
+pub struct SignalPolicy {
+    /// Stop the inferior and return to the prompt
+    pub stop: bool,
+    /// Say that the signal arrived
+    pub print: bool,
+    /// Deliver the signal to the inferior when it is resumed
+    pub pass: bool,
+}
+
+/// The `handle` table: how each signal the inferior receives is treated.
+pub struct SignalTable {
+    policies: HashMap<Signal, SignalPolicy>,
+}
+
+impl SignalTable {
+    /// Creates a table with gdb's defaults: signals that are part of normal operation (timers,
+    /// child exits, window resizes, ...) are passed along silently, everything else stops the
+    /// inferior. SIGINT and SIGTRAP are the debugger's own, so they are not passed on.
+    pub fn new() -> SignalTable {
+        let mut policies = HashMap::new();
+        for signal in Signal::iterator() {
+            let policy = match signal {
+                Signal::SIGALRM
+                | Signal::SIGURG
+                | Signal::SIGCHLD
+                | Signal::SIGWINCH
+                | Signal::SIGIO
+                | Signal::SIGVTALRM
+                | Signal::SIGPROF => SignalPolicy {
+                    stop: false,
+                    print: false,
+                    pass: true,
+                },
+                Signal::SIGINT | Signal::SIGTRAP => SignalPolicy {
+                    stop: true,
+                    print: true,
+                    pass: false,
+                },
+                _ => SignalPolicy {
+                    stop: true,
+                    print: true,
+                    pass: true,
+                },
+            };
+            policies.insert(signal, policy);
+        }
+        SignalTable { policies }
+    }
+
+    pub fn get(&self, signal: Signal) -> SignalPolicy {
+        self.policies[&signal]
+    }
+
+    /// Applies `handle` keywords to `signal`'s policy. Like gdb, `stop` implies `print` and
+    /// `noprint` implies `nostop`. Returns the first keyword that isn't understood, if any, in
+    /// which case the policy is left as it was.
+    pub fn update(&mut self, signal: Signal, keywords: &[String]) -> Result<(), String> {
+        let mut policy = self.policies[&signal];
+        for keyword in keywords {
+            match keyword.as_str() {
+                "stop" => {
+                    policy.stop = true;
+                    policy.print = true;
+                }
+                "nostop" => policy.stop = false,
+                "print" => policy.print = true,
+                "noprint" => {
+                    policy.print = false;
+                    policy.stop = false;
+                }
+                "pass" | "noignore" => policy.pass = true,
+                "nopass" | "ignore" => policy.pass = false,
+                _ => return Err(keyword.clone()),
+            }
+        }
+        self.policies.insert(signal, policy);
+        Ok(())
+    }
+
+    /// Prints the policies of `signals` as a table.
+    pub fn print(&self, signals: &[Signal]) {
+        let yes_no = |flag: bool| if flag { "Yes" } else { "No" };
+        println!("Signal        Stop\tPrint\tPass to program");
+        for signal in signals {
+            let policy = self.get(*signal);
+            println!(
+                "{:<14}{}\t{}\t{}",
+                signal.as_str(),
+                yes_no(policy.stop),
+                yes_no(policy.print),
+                yes_no(policy.pass)
+            );
+        }
+    }
+}
+
+/// Parses a signal given as `SIGUSR1`, `USR1` (in any case) or a number.
+pub fn parse_signal(text: &str) -> Option<Signal> {
+    if let Ok(number) = text.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+    let name = text.to_uppercase();
+    if name.starts_with("SIG") {
+        name.parse().ok()
+    } else {
+        format!("SIG{}", name).parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn stop_implies_print() {
+        let mut table = SignalTable::new();
+        assert!(!table.get(Signal::SIGALRM).print);
+        table.update(Signal::SIGALRM, &keywords(&["stop"])).unwrap();
+        let policy = table.get(Signal::SIGALRM);
+        assert!(policy.stop && policy.print && policy.pass);
+    }
+
+    #[test]
+    fn noprint_implies_nostop() {
+        let mut table = SignalTable::new();
+        table
+            .update(Signal::SIGUSR1, &keywords(&["noprint", "nopass"]))
+            .unwrap();
+        let policy = table.get(Signal::SIGUSR1);
+        assert!(!policy.stop && !policy.print && !policy.pass);
+        // Later keywords win
+        table
+            .update(Signal::SIGUSR1, &keywords(&["nostop", "print", "pass"]))
+            .unwrap();
+        let policy = table.get(Signal::SIGUSR1);
+        assert!(!policy.stop && policy.print && policy.pass);
+    }
+
+    #[test]
+    fn bad_keyword_changes_nothing() {
+        let mut table = SignalTable::new();
+        assert_eq!(
+            table.update(Signal::SIGUSR1, &keywords(&["nostop", "bogus", "nopass"])),
+            Err("bogus".to_string())
+        );
+        let policy = table.get(Signal::SIGUSR1);
+        assert!(policy.stop && policy.print && policy.pass);
+    }
+
+    #[test]
+    fn parse_signal_names_and_numbers() {
+        assert_eq!(parse_signal("usr1"), Some(Signal::SIGUSR1));
+        assert_eq!(parse_signal("SIGUSR1"), Some(Signal::SIGUSR1));
+        assert_eq!(parse_signal("SigSegv"), Some(Signal::SIGSEGV));
+        assert_eq!(parse_signal("10"), Some(Signal::SIGUSR1));
+        assert_eq!(parse_signal("garbage"), None);
+        assert_eq!(parse_signal("SIG"), None);
+        assert_eq!(parse_signal("0"), None);
+    }
+}
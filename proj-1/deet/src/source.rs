@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Reads source files named in the debug info, looking them up in the user's source directories
+/// when the path recorded at compile time doesn't exist here (e.g. because the binary was built
+/// inside the container).
+pub struct SourceFiles {
+    directories: Vec<PathBuf>,
+    /// The lines of each file we have read, or None if it couldn't be found
+    cache: HashMap<String, Option<Vec<String>>>,
+}
+
+impl SourceFiles {
+    pub fn new() -> SourceFiles {
+        SourceFiles {
+            directories: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Adds a directory to search, ahead of the ones added before it.
+    pub fn add_directory(&mut self, directory: &str) {
+        self.directories.insert(0, PathBuf::from(directory));
+        self.cache.clear();
+    }
+
+    /// Forgets all source directories.
+    pub fn clear_directories(&mut self) {
+        self.directories.clear();
+        self.cache.clear();
+    }
+
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Returns the lines of `file`, or None if it can't be found.
+    pub fn lines(&mut self, file: &str) -> Option<&[String]> {
+        if !self.cache.contains_key(file) {
+            let lines = self.find(file).and_then(|path| {
+                let contents = fs::read(path).ok()?;
+                Some(
+                    String::from_utf8_lossy(&contents)
+                        .lines()
+                        .map(|line| line.to_string())
+                        .collect(),
+                )
+            });
+            self.cache.insert(file.to_string(), lines);
+        }
+        self.cache[file].as_deref()
+    }
+
+    /// Finds `file` on disk. Each source directory is tried with ever shorter suffixes of the
+    /// recorded path, so `/deet/samples/foo.c` can be found as `<dir>/samples/foo.c` or
+    /// `<dir>/foo.c`.
+    fn find(&self, file: &str) -> Option<PathBuf> {
+        for directory in &self.directories {
+            let components: Vec<_> = Path::new(file)
+                .components()
+                .filter(|component| match component {
+                    std::path::Component::Normal(_) => true,
+                    _ => false,
+                })
+                .collect();
+            for start in 0..components.len() {
+                let mut candidate = directory.clone();
+                candidate.extend(&components[start..]);
+                if candidate.is_file() {
+                    return Some(candidate);
+                }
+            }
+        }
+        let path = PathBuf::from(file);
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Stack unwinding based on call frame information (CFI): the `.eh_frame` and `.debug_frame`
+//! tables that tell us, for any instruction, where the caller's return address and saved
+//! registers live. Unlike walking the rbp chain, this also works for code built with
+//! `-fomit-frame-pointer`.
+
+use gimli::{BaseAddresses, CfaRule, RegisterRule, UninitializedUnwindContext, UnwindSection};
+use object::{Object, ObjectSection, ObjectSegment};
+use std::fs;
+use std::rc::Rc;
+
+type Reader = gimli::EndianRcSlice<gimli::RunTimeEndian>;
+
+// DWARF register numbers on x86-64
+const RBP: gimli::Register = gimli::Register(6);
+const RSP: gimli::Register = gimli::Register(7);
+const RETURN_ADDRESS: gimli::Register = gimli::Register(16);
+/// rbx and r12-r15, which a function must preserve for its caller besides rbp and rsp
+pub const CALLEE_SAVED: [u16; 5] = [3, 12, 13, 14, 15];
+
+/// The registers that identify a stack frame and that are needed to find its caller.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Registers {
+    pub rip: usize,
+    pub rsp: usize,
+    pub rbp: usize,
+    /// The values of the `CALLEE_SAVED` registers in this frame, or None where the CFI doesn't
+    /// say what the frame's callee did with one
+    pub callee_saved: [Option<usize>; 5],
+}
+
+impl Registers {
+    /// Takes the registers of the innermost frame from the full register set.
+    pub fn from_user_regs(regs: &libc::user_regs_struct) -> Registers {
+        Registers {
+            rip: regs.rip as usize,
+            rsp: regs.rsp as usize,
+            rbp: regs.rbp as usize,
+            callee_saved: [
+                Some(regs.rbx as usize),
+                Some(regs.r12 as usize),
+                Some(regs.r13 as usize),
+                Some(regs.r14 as usize),
+                Some(regs.r15 as usize),
+            ],
+        }
+    }
+}
+
+/// The result of unwinding one frame.
+#[derive(Debug)]
+pub enum Unwind {
+    /// The caller's registers.
+    Caller(Registers),
+    /// The CFI says this frame has no caller (e.g. `_start`).
+    Outermost,
+    /// There is no usable CFI for this frame.
+    Unknown,
+}
+
+/// The CFI tables of one object file.
+pub struct CallFrameInfo {
+    eh_frame: Option<(gimli::EhFrame<Reader>, BaseAddresses)>,
+    debug_frame: Option<(gimli::DebugFrame<Reader>, BaseAddresses)>,
+    /// How far the object was loaded from the addresses it was linked at
+    bias: usize,
+}
+
+impl CallFrameInfo {
+    pub fn from_object(object: &object::File, bias: usize) -> CallFrameInfo {
+        let endian = if object.is_little_endian() {
+            gimli::RunTimeEndian::Little
+        } else {
+            gimli::RunTimeEndian::Big
+        };
+        let section = |name: &str| -> Option<(Reader, u64)> {
+            let data = object.section_data_by_name(name)?;
+            let address = object.section_by_name(name)?.address();
+            Some((Reader::new(Rc::from(&*data), endian), address))
+        };
+        let text_address = object
+            .section_by_name(".text")
+            .map_or(0, |text| text.address());
+
+        let eh_frame = section(".eh_frame").map(|(data, address)| {
+            let mut eh_frame = gimli::EhFrame::from(data);
+            eh_frame.set_address_size(8);
+            let bases = BaseAddresses::default()
+                .set_eh_frame(address)
+                .set_text(text_address);
+            (eh_frame, bases)
+        });
+        let debug_frame = section(".debug_frame").map(|(data, _)| {
+            let mut debug_frame = gimli::DebugFrame::from(data);
+            debug_frame.set_address_size(8);
+            (debug_frame, BaseAddresses::default().set_text(text_address))
+        });
+        CallFrameInfo {
+            eh_frame,
+            debug_frame,
+            bias,
+        }
+    }
+
+    /// Loads the CFI of the object file at `path`, which was loaded so that `start` is the
+    /// address of its first segment.
+    pub fn from_file(path: &str, start: usize) -> Option<CallFrameInfo> {
+        let file = fs::File::open(path).ok()?;
+        let mmap = unsafe { memmap::Mmap::map(&file).ok()? };
+        let object = object::File::parse(&*mmap).ok()?;
+        let first_segment = object
+            .segments()
+            .map(|segment| segment.address() as usize)
+            .min()
+            .unwrap_or(0);
+        // Mappings are page aligned, while the segment may not be
+        let bias = start.wrapping_sub(first_segment & !0xfff);
+        Some(CallFrameInfo::from_object(&object, bias))
+    }
+
+    /// Computes the registers of the caller of the frame described by `regs`, using `read_word` to
+    /// read the stack. `is_innermost` says whether `regs.rip` is the current instruction; for
+    /// outer frames it is a return address, which may already lie past the end of the calling
+    /// function.
+    pub fn unwind<F>(&self, regs: &Registers, is_innermost: bool, read_word: F) -> Unwind
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let lookup_rip = if is_innermost { regs.rip } else { regs.rip - 1 };
+        let address = lookup_rip.wrapping_sub(self.bias) as u64;
+        let mut ctx = UninitializedUnwindContext::new();
+        let row = match &self.eh_frame {
+            Some((eh_frame, bases)) => eh_frame
+                .unwind_info_for_address(bases, &mut ctx, address, gimli::EhFrame::cie_from_offset)
+                .ok(),
+            None => None,
+        };
+        let row = match (row, &self.debug_frame) {
+            (Some(row), _) => row,
+            (None, Some((debug_frame, bases))) => {
+                match debug_frame.unwind_info_for_address(
+                    bases,
+                    &mut ctx,
+                    address,
+                    gimli::DebugFrame::cie_from_offset,
+                ) {
+                    Ok(row) => row,
+                    Err(_) => return Unwind::Unknown,
+                }
+            }
+            (None, None) => return Unwind::Unknown,
+        };
+
+        let cfa = match row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => {
+                let base = match *register {
+                    RSP => regs.rsp,
+                    RBP => regs.rbp,
+                    _ => return Unwind::Unknown,
+                };
+                (base as i64 + offset) as usize
+            }
+            CfaRule::Expression(_) => return Unwind::Unknown,
+        };
+        let saved = |rule: RegisterRule<Reader>, current: Option<usize>| -> Option<usize> {
+            match rule {
+                RegisterRule::Undefined | RegisterRule::SameValue => current,
+                RegisterRule::Offset(offset) => read_word((cfa as i64 + offset) as usize),
+                RegisterRule::ValOffset(offset) => Some((cfa as i64 + offset) as usize),
+                _ => None,
+            }
+        };
+
+        let rip = match row.register(RETURN_ADDRESS) {
+            RegisterRule::Undefined => return Unwind::Outermost,
+            rule => match saved(rule, Some(regs.rip)) {
+                Some(rip) => rip,
+                None => return Unwind::Unknown,
+            },
+        };
+        let rbp = match saved(row.register(RBP), Some(regs.rbp)) {
+            Some(rbp) => rbp,
+            None => return Unwind::Unknown,
+        };
+        if rip == 0 {
+            return Unwind::Outermost;
+        }
+        let mut callee_saved = [None; 5];
+        for (i, register) in CALLEE_SAVED.iter().enumerate() {
+            callee_saved[i] = saved(
+                row.register(gimli::Register(*register)),
+                regs.callee_saved[i],
+            );
+        }
+        Unwind::Caller(Registers {
+            rip,
+            rsp: cfa,
+            rbp,
+            callee_saved,
+        })
+    }
+}